@@ -1,11 +1,25 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use unicode_normalization::UnicodeNormalization;
 
-const MAX_MEMORY_ENTRIES: usize = 500;
+use crate::apple_music::TrackInfo;
+
+pub const DEFAULT_MAX_MEMORY_ENTRIES: usize = 500;
+pub const DEFAULT_MAX_DISK_ENTRIES: usize = 5000;
+/// iTunes results fetched per lookup and ranked against the query.
+pub const DEFAULT_ITUNES_RESULT_LIMIT: u32 = 5;
 const DISK_TTL_SECS: u64 = 30 * 24 * 60 * 60; // 30 days
+/// Expired disk entries are kept this long as a fallback while offline.
+const STALE_TTL_SECS: u64 = 365 * 24 * 60 * 60; // 1 year
+/// Disk entries this close to expiry are eligible for a background refresh.
+const REFRESH_WINDOW_SECS: u64 = 3 * 24 * 60 * 60; // 3 days
 const MIN_REQUEST_INTERVAL_MS: u64 = 1000;
+/// How long a reachability check of an artwork host is trusted.
+const REACHABILITY_TTL_SECS: u64 = 5 * 60;
+/// How long the result of the iTunes connectivity check is trusted.
+const ONLINE_CHECK_TTL_SECS: u64 = 60;
 
 // --- Disk cache ---
 
@@ -13,6 +27,8 @@ const MIN_REQUEST_INTERVAL_MS: u64 = 1000;
 struct DiskCacheEntry {
     url: String,
     fetched_at: u64,
+    #[serde(default)]
+    explicit: bool,
 }
 
 #[derive(Serialize, Deserialize, Default)]
@@ -25,6 +41,7 @@ struct DiskCache {
 struct MemoryCacheEntry {
     url: String,
     inserted_at: Instant,
+    explicit: bool,
 }
 
 // --- iTunes API response ---
@@ -36,10 +53,51 @@ struct ItunesSearchResponse {
 
 #[derive(Deserialize)]
 struct ItunesResult {
+    #[serde(rename = "collectionName")]
+    collection_name: Option<String>,
+    #[serde(rename = "artistName")]
+    artist_name: Option<String>,
     #[serde(rename = "artworkUrl100")]
     artwork_url_100: Option<String>,
+    #[serde(rename = "collectionExplicitness")]
+    collection_explicitness: Option<String>,
+}
+
+// --- Cache stats ---
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub memory_entries: usize,
+    pub disk_entries: usize,
+    pub max_memory_entries: usize,
+    pub max_disk_entries: usize,
+}
+
+/// Outcome of `import_disk_cache`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheImport {
+    pub added: usize,
+    pub updated: usize,
+    /// Entries that were expired, malformed, or older than the local copy.
+    pub skipped: usize,
+}
+
+// --- Progress ---
+
+/// Resolution progress, reported to the listener set with `set_progress_listener`.
+pub enum ArtProgress<'a> {
+    /// A lookup that isn't a memory cache hit has started.
+    Resolving { key: &'a str },
+    Resolved { key: &'a str, url: Option<&'a str> },
 }
 
+pub type ProgressListener = Box<dyn Fn(ArtProgress<'_>) + Send + Sync>;
+
+/// Repeated `Resolving` reports for the same key within this window are dropped.
+const PROGRESS_DEBOUNCE: std::time::Duration = std::time::Duration::from_secs(1);
+
 // --- Resolver ---
 
 pub struct AlbumArtResolver {
@@ -48,19 +106,146 @@ pub struct AlbumArtResolver {
     disk_cache_dirty: bool,
     disk_cache_path: PathBuf,
     client: reqwest::Client,
-    last_request_at: Option<Instant>,
+    /// Rate limiter state; see `enforce_rate_limit`.
+    tokens: f64,
+    burst_capacity: u32,
+    last_refill: Instant,
+    min_art_bytes: Option<u64>,
+    itunes_result_limit: u32,
+    max_memory_entries: usize,
+    max_disk_entries: usize,
+    disabled: bool,
+    hide_explicit: bool,
+    verify_reachable: bool,
+    /// Last reachability check per artwork host.
+    reachability: HashMap<String, (Instant, bool)>,
+    serve_stale_offline: bool,
+    /// Resolve from the caches only, never hitting the network; see `set_cache_only`.
+    cache_only: bool,
+    /// Last iTunes connectivity check; see `is_online`.
+    last_online_check: Option<(Instant, bool)>,
+    /// Keys currently served from an expired disk entry, refreshed once back online.
+    stale_keys: HashSet<String>,
+    progress: Option<ProgressListener>,
+    /// Key and time of the last `Resolving` report, for debouncing.
+    last_resolving: Option<(String, Instant)>,
 }
 
-fn cache_key(artist: &str, album: &str) -> String {
-    let artist_clean = artist.to_lowercase().trim().to_string();
-    let album_clean = album.to_lowercase().trim().to_string();
-    if album_clean.is_empty() {
-        artist_clean
+/// Singles either have no album or an album named after the track (often with a
+/// " - Single" suffix). These are keyed by track too, so unrelated singles by the
+/// same artist don't share a cover.
+fn is_single(album: &str, track: &str) -> bool {
+    let album = album.trim();
+    let album = album.strip_suffix(" - Single").unwrap_or(album).trim();
+    album.is_empty() || album.eq_ignore_ascii_case(track.trim())
+}
+
+/// NFC-normalize before lowercasing so decomposed names (as macOS often stores them) share
+/// a key with their precomposed form.
+fn key_part(s: &str) -> String {
+    s.nfc().collect::<String>().to_lowercase().trim().to_string()
+}
+
+/// Lowercased alphanumeric words of `s`, for fuzzy comparison.
+fn match_words(s: &str) -> HashSet<String> {
+    key_part(s)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Dice coefficient over the words of `a` and `b`: 1.0 for the same words, 0.0 for none
+/// in common.
+fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (match_words(a), match_words(b));
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let common = a.intersection(&b).count();
+    2.0 * common as f64 / (a.len() + b.len()) as f64
+}
+
+/// Pick the result closest to the queried artist and album. The album counts double,
+/// since it's what tells editions apart; ties keep iTunes' own order.
+fn best_itunes_match<'a>(
+    results: &'a [ItunesResult],
+    artist: &str,
+    album: &str,
+) -> Option<&'a ItunesResult> {
+    let score = |result: &ItunesResult| {
+        let album_score = if album.is_empty() {
+            0.0
+        } else {
+            similarity(album, result.collection_name.as_deref().unwrap_or(""))
+        };
+        2.0 * album_score + similarity(artist, result.artist_name.as_deref().unwrap_or(""))
+    };
+    results
+        .iter()
+        .filter(|result| result.artwork_url_100.is_some())
+        .fold(None, |best: Option<(&ItunesResult, f64)>, result| {
+            let s = score(result);
+            match best {
+                Some((_, best_score)) if best_score >= s => best,
+                _ => Some((result, s)),
+            }
+        })
+        .map(|(result, _)| result)
+}
+
+fn cache_key(artist: &str, album: &str, track: &str) -> String {
+    let artist_clean = key_part(artist);
+    let album_clean = key_part(album);
+    if is_single(album, track) {
+        let track_clean = key_part(track);
+        format!("{artist_clean}::{album_clean}::{track_clean}")
     } else {
         format!("{artist_clean}::{album_clean}")
     }
 }
 
+/// Markers that introduce featured artists in an artist credit.
+const FEATURE_MARKERS: &[&str] = &[" (feat", " (ft.", " feat. ", " feat ", " ft. ", " featuring "];
+
+/// The primary artist of a credit like "Artist A feat. Artist B".
+fn primary_artist(artist: &str) -> &str {
+    let lower = artist.to_ascii_lowercase();
+    let cut = FEATURE_MARKERS
+        .iter()
+        .filter_map(|marker| lower.find(marker))
+        .min()
+        .unwrap_or(artist.len());
+    let primary = artist[..cut].trim();
+    if primary.is_empty() {
+        artist
+    } else {
+        primary
+    }
+}
+
+/// Artist used for the iTunes query. Compilations and box sets match their cover far
+/// better by album artist than by the (often guest) track artist, and featured artists
+/// only dilute the search. Display keeps the full `track.artist`.
+pub fn query_artist(track: &TrackInfo) -> &str {
+    let artist = track
+        .album_artist
+        .as_deref()
+        .filter(|a| !a.trim().is_empty())
+        .unwrap_or(&track.artist);
+    primary_artist(artist)
+}
+
+/// iTunes search term for `artist` and `album`; a blank album searches by artist alone.
+fn itunes_search_term(artist: &str, album: &str) -> String {
+    let album = album.trim();
+    if album.is_empty() {
+        artist.to_string()
+    } else {
+        format!("{artist} {album}")
+    }
+}
+
 fn now_unix_secs() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -106,10 +291,102 @@ impl AlbumArtResolver {
             disk_cache_dirty: false,
             disk_cache_path,
             client,
-            last_request_at: None,
+            tokens: 1.0,
+            burst_capacity: 1,
+            last_refill: Instant::now(),
+            min_art_bytes: None,
+            itunes_result_limit: DEFAULT_ITUNES_RESULT_LIMIT,
+            max_memory_entries: DEFAULT_MAX_MEMORY_ENTRIES,
+            max_disk_entries: DEFAULT_MAX_DISK_ENTRIES,
+            disabled: false,
+            hide_explicit: false,
+            verify_reachable: false,
+            reachability: HashMap::new(),
+            serve_stale_offline: true,
+            cache_only: false,
+            last_online_check: None,
+            stale_keys: HashSet::new(),
+            progress: None,
+            last_resolving: None,
         }
     }
 
+    /// Disable all art resolution; `resolve` always returns `None` (the logo).
+    pub fn set_disabled(&mut self, disabled: bool) {
+        self.disabled = disabled;
+    }
+
+    /// Withhold artwork for albums iTunes marks explicit, so the logo is shown instead.
+    pub fn set_hide_explicit(&mut self, hide_explicit: bool) {
+        self.hide_explicit = hide_explicit;
+    }
+
+    /// Check that the artwork host is reachable before handing out a URL, so a blocked
+    /// CDN falls back to the logo instead of a broken cover.
+    pub fn set_verify_reachable(&mut self, verify_reachable: bool) {
+        self.verify_reachable = verify_reachable;
+    }
+
+    /// While iTunes can't be reached, serve expired disk entries instead of the logo.
+    pub fn set_serve_stale_offline(&mut self, serve_stale_offline: bool) {
+        self.serve_stale_offline = serve_stale_offline;
+    }
+
+    /// Skip iTunes lookups and background refreshes, using only cached art (expired entries
+    /// included) or the logo. Used to save power on battery.
+    pub fn set_cache_only(&mut self, cache_only: bool) {
+        self.cache_only = cache_only;
+    }
+
+    /// Allow up to `concurrency` iTunes requests back to back while keeping the
+    /// sustained rate at one per second.
+    pub fn set_art_concurrency(&mut self, concurrency: u32) {
+        self.burst_capacity = concurrency.max(1);
+        self.tokens = self.tokens.min(self.burst_capacity as f64);
+    }
+
+    /// Fetch up to `limit` iTunes results per lookup; the closest match wins.
+    pub fn set_itunes_result_limit(&mut self, limit: u32) {
+        self.itunes_result_limit = limit.max(1);
+    }
+
+    pub fn set_progress_listener(&mut self, listener: ProgressListener) {
+        self.progress = Some(listener);
+    }
+
+    fn report_resolving(&mut self, key: &str) {
+        let Some(listener) = &self.progress else {
+            return;
+        };
+        let recent = self
+            .last_resolving
+            .as_ref()
+            .is_some_and(|(last, at)| last == key && at.elapsed() < PROGRESS_DEBOUNCE);
+        if !recent {
+            listener(ArtProgress::Resolving { key });
+            self.last_resolving = Some((key.to_string(), Instant::now()));
+        }
+    }
+
+    pub fn set_cache_limits(&mut self, max_memory_entries: usize, max_disk_entries: usize) {
+        self.max_memory_entries = max_memory_entries.max(1);
+        self.max_disk_entries = max_disk_entries.max(1);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            memory_entries: self.memory_cache.len(),
+            disk_entries: self.disk_cache.entries.len(),
+            max_memory_entries: self.max_memory_entries,
+            max_disk_entries: self.max_disk_entries,
+        }
+    }
+
+    /// Reject artwork whose reported size is below `min_bytes`. `None` disables the check.
+    pub fn set_min_art_bytes(&mut self, min_bytes: Option<u64>) {
+        self.min_art_bytes = min_bytes;
+    }
+
     fn load_disk_cache(path: &PathBuf) -> DiskCache {
         let data = match std::fs::read_to_string(path) {
             Ok(d) => d,
@@ -124,55 +401,159 @@ impl AlbumArtResolver {
             }
         };
 
-        // Prune expired entries
+        // Prune entries too old even for the offline fallback
         let now = now_unix_secs();
         cache.entries.retain(|_, entry| {
-            now.saturating_sub(entry.fetched_at) < DISK_TTL_SECS
+            now.saturating_sub(entry.fetched_at) < STALE_TTL_SECS
         });
 
         cache
     }
 
-    pub async fn resolve(&mut self, artist: &str, album: &str) -> Option<String> {
-        let key = cache_key(artist, album);
+    pub async fn resolve(&mut self, artist: &str, album: &str, track: &str) -> Option<String> {
+        if self.disabled {
+            return None;
+        }
+
+        let key = cache_key(artist, album, track);
+        if !self.memory_cache.contains_key(&key) {
+            self.report_resolving(&key);
+        }
+
+        let mut url = self.lookup(artist, album, track).await;
+        if let Some(found) = &url {
+            if self.verify_reachable && !self.cache_only && !self.is_reachable(found).await {
+                url = None;
+            }
+        }
+
+        if let Some(listener) = &self.progress {
+            listener(ArtProgress::Resolved {
+                key: &key,
+                url: url.as_deref(),
+            });
+        }
+        url
+    }
+
+    async fn lookup(&mut self, artist: &str, album: &str, track: &str) -> Option<String> {
+        let key = cache_key(artist, album, track);
 
         // 1. Memory cache
         if let Some(entry) = self.memory_cache.get(&key) {
-            tracing::debug!("Art cache hit (memory): {key}");
-            return Some(entry.url.clone());
+            let (url, explicit) = (entry.url.clone(), entry.explicit);
+            if !self.stale_keys.contains(&key) || self.cache_only || !self.is_online().await {
+                tracing::debug!("Art cache hit (memory): {key}");
+                return self.unless_hidden(url, explicit);
+            }
+            tracing::debug!("Back online; refreshing stale art: {key}");
+            self.memory_cache.remove(&key);
         }
 
         // 2. Disk cache
         if let Some(entry) = self.disk_cache.entries.get(&key) {
             let now = now_unix_secs();
             if now.saturating_sub(entry.fetched_at) < DISK_TTL_SECS {
-                let url = entry.url.clone();
+                let (url, explicit) = (entry.url.clone(), entry.explicit);
                 tracing::debug!("Art cache hit (disk): {key}");
-                self.insert_memory_cache(key, url.clone());
-                return Some(url);
+                self.insert_memory_cache(key, url.clone(), explicit);
+                return self.unless_hidden(url, explicit);
             }
         }
 
-        // 3. Fetch from iTunes
-        let url = self.fetch_from_itunes(artist, album).await?;
-        self.insert_memory_cache(key.clone(), url.clone());
-        self.insert_disk_cache(key, url.clone());
+        // 2b. Expired disk entry: keep showing it while offline or cache-only
+        let stale = self
+            .disk_cache
+            .entries
+            .get(&key)
+            .map(|entry| (entry.url.clone(), entry.explicit));
+        let use_stale = self.serve_stale_offline || self.cache_only;
+        if let Some((url, explicit)) = stale.filter(|_| use_stale) {
+            if self.cache_only || !self.is_online().await {
+                tracing::debug!("Offline or cache-only; serving stale art: {key}");
+                self.stale_keys.insert(key.clone());
+                self.insert_memory_cache(key, url.clone(), explicit);
+                return self.unless_hidden(url, explicit);
+            }
+        }
+        if self.cache_only {
+            tracing::debug!("Cache-only; skipping iTunes lookup: {key}");
+            return None;
+        }
+        self.stale_keys.remove(&key);
+
+        // 3. Fetch from iTunes. Albumless singles are searched by track name.
+        let query_album = if album.trim().is_empty() { track } else { album };
+        let (url, explicit) = self.fetch_from_itunes(artist, query_album).await?;
+        self.insert_memory_cache(key.clone(), url.clone(), explicit);
+        self.insert_disk_cache(key, url.clone(), explicit);
         self.save_disk_cache_if_dirty();
+        self.unless_hidden(url, explicit)
+    }
+
+    fn unless_hidden(&self, url: String, explicit: bool) -> Option<String> {
+        if explicit && self.hide_explicit {
+            tracing::debug!("Hiding explicit artwork: {url}");
+            return None;
+        }
         Some(url)
     }
 
-    async fn fetch_from_itunes(&mut self, artist: &str, album: &str) -> Option<String> {
+    /// Drop the cached artwork for one track from both caches.
+    pub fn forget(&mut self, artist: &str, album: &str, track: &str) {
+        let key = cache_key(artist, album, track);
+        self.memory_cache.remove(&key);
+        self.stale_keys.remove(&key);
+        if self.disk_cache.entries.remove(&key).is_some() {
+            self.disk_cache_dirty = true;
+            self.save_disk_cache_if_dirty();
+        }
+    }
+
+    /// Cache an artwork URL supplied by the music source, so it's kept alongside
+    /// iTunes results.
+    pub fn remember(&mut self, artist: &str, album: &str, track: &str, url: &str) {
+        let key = cache_key(artist, album, track);
+        if self.memory_cache.get(&key).is_some_and(|e| e.url == url) {
+            return;
+        }
+        self.insert_memory_cache(key.clone(), url.to_string(), false);
+        self.insert_disk_cache(key, url.to_string(), false);
+        self.save_disk_cache_if_dirty();
+    }
+
+    /// Returns the artwork URL and whether iTunes marks the album explicit.
+    async fn fetch_from_itunes(&mut self, artist: &str, album: &str) -> Option<(String, bool)> {
+        self.enforce_rate_limit().await;
+        self.query_itunes(artist, album).await
+    }
+
+    /// Time one iTunes lookup for a track, bypassing and leaving both caches untouched.
+    /// The rate limiter's wait isn't counted. `None` if the lookup found nothing or failed.
+    pub async fn time_uncached_lookup(
+        &mut self,
+        artist: &str,
+        album: &str,
+        track: &str,
+    ) -> Option<std::time::Duration> {
+        if self.disabled {
+            return None;
+        }
+        let query_album = if album.trim().is_empty() { track } else { album };
         self.enforce_rate_limit().await;
+        let started = Instant::now();
+        self.query_itunes(artist, query_album).await?;
+        Some(started.elapsed())
+    }
 
+    /// The iTunes search behind `fetch_from_itunes`, without rate limiting.
+    async fn query_itunes(&mut self, artist: &str, album: &str) -> Option<(String, bool)> {
         let album_trimmed = album.trim();
-        let query = if album_trimmed.is_empty() {
-            artist.to_string()
-        } else {
-            format!("{} {}", artist, album_trimmed)
-        };
+        let query = itunes_search_term(artist, album_trimmed);
         let url = format!(
-            "https://itunes.apple.com/search?term={}&media=music&entity=album&limit=1",
-            urlencode(&query)
+            "https://itunes.apple.com/search?term={}&media=music&entity=album&limit={}",
+            urlencode(&query),
+            self.itunes_result_limit
         );
 
         tracing::info!("Fetching album art from iTunes: {url}");
@@ -193,56 +574,262 @@ impl AlbumArtResolver {
             }
         };
 
-        let artwork_url = body.results.first()?.artwork_url_100.as_ref()?;
+        let result = best_itunes_match(&body.results, artist, album_trimmed)?;
+        let artwork_url = result.artwork_url_100.as_ref()?;
+        let explicit = result.collection_explicitness.as_deref() == Some("explicit");
 
         // Upscale from 100x100 to 512x512
         let hires = artwork_url.replace("100x100bb", "512x512bb");
-        Some(hires)
+
+        if let Some(min_bytes) = self.min_art_bytes {
+            if !self.art_meets_min_size(&hires, min_bytes).await {
+                return None;
+            }
+        }
+
+        Some((hires, explicit))
     }
 
-    async fn enforce_rate_limit(&mut self) {
-        if let Some(last) = self.last_request_at {
-            let elapsed = last.elapsed().as_millis() as u64;
-            if elapsed < MIN_REQUEST_INTERVAL_MS {
-                let wait = MIN_REQUEST_INTERVAL_MS - elapsed;
-                tokio::time::sleep(std::time::Duration::from_millis(wait)).await;
+    /// HEAD the artwork URL and compare its Content-Length against `min_bytes`.
+    /// Placeholder covers are tiny; if the size can't be determined we accept the art.
+    async fn art_meets_min_size(&self, url: &str, min_bytes: u64) -> bool {
+        let resp = match self.client.head(url).send().await {
+            Ok(r) => r,
+            Err(e) => {
+                tracing::debug!("Artwork size check failed, accepting art: {e}");
+                return true;
+            }
+        };
+
+        // Read the header directly; `content_length()` reflects the (empty) HEAD body.
+        let content_length = resp
+            .headers()
+            .get(reqwest::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+
+        match content_length {
+            Some(len) if len < min_bytes => {
+                tracing::info!("Rejecting artwork below minimum size ({len} < {min_bytes} bytes): {url}");
+                false
             }
+            _ => true,
+        }
+    }
+
+    /// Key of the oldest disk entry nearing expiry, skipping keys in `skip`.
+    pub fn next_expiring_key(&self, skip: &HashSet<String>) -> Option<String> {
+        if self.disabled || self.cache_only {
+            return None;
+        }
+        let now = now_unix_secs();
+        self.disk_cache
+            .entries
+            .iter()
+            .filter(|(key, entry)| {
+                let age = now.saturating_sub(entry.fetched_at);
+                age >= DISK_TTL_SECS - REFRESH_WINDOW_SECS && !skip.contains(*key)
+            })
+            .min_by_key(|(_, entry)| entry.fetched_at)
+            .map(|(key, _)| key.clone())
+    }
+
+    /// Re-fetch the artwork for a cache key from iTunes, renewing its TTL.
+    /// Returns whether the entry was refreshed.
+    pub async fn refresh_entry(&mut self, key: &str) -> bool {
+        // Keys are `artist::album` or `artist::album::track` (see `cache_key`)
+        let mut parts = key.splitn(3, "::");
+        let (Some(artist), Some(album)) = (parts.next(), parts.next()) else {
+            return false;
+        };
+        let track = parts.next().unwrap_or_default();
+        let query_album = if album.trim().is_empty() { track } else { album };
+
+        let Some((url, explicit)) = self.fetch_from_itunes(artist, query_album).await else {
+            return false;
+        };
+        tracing::debug!("Refreshed expiring art cache entry: {key}");
+        self.stale_keys.remove(key);
+        if self.memory_cache.remove(key).is_some() {
+            self.insert_memory_cache(key.to_string(), url.clone(), explicit);
+        }
+        self.insert_disk_cache(key.to_string(), url, explicit);
+        self.save_disk_cache_if_dirty();
+        true
+    }
+
+    /// Whether iTunes can be reached at all. Any HTTP response counts as online; only a
+    /// failed connection doesn't.
+    async fn is_online(&mut self) -> bool {
+        if let Some((checked_at, online)) = self.last_online_check {
+            if checked_at.elapsed().as_secs() < ONLINE_CHECK_TTL_SECS {
+                return online;
+            }
+        }
+        let online = self
+            .client
+            .head("https://itunes.apple.com/")
+            .timeout(std::time::Duration::from_secs(3))
+            .send()
+            .await
+            .is_ok();
+        if !online {
+            tracing::info!("iTunes unreachable; treating as offline");
+        }
+        self.last_online_check = Some((Instant::now(), online));
+        online
+    }
+
+    /// HEAD the artwork URL, reusing a recent result for the same host.
+    async fn is_reachable(&mut self, url: &str) -> bool {
+        let host = url
+            .split_once("://")
+            .map_or(url, |(_, rest)| rest)
+            .split('/')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        if let Some((checked_at, reachable)) = self.reachability.get(&host) {
+            if checked_at.elapsed().as_secs() < REACHABILITY_TTL_SECS {
+                return *reachable;
+            }
+        }
+
+        let reachable = match self.client.head(url).send().await {
+            Ok(resp) => resp.status().is_success(),
+            Err(e) => {
+                tracing::warn!("Artwork host {host} unreachable, using logo: {e}");
+                false
+            }
+        };
+        self.reachability.insert(host, (Instant::now(), reachable));
+        reachable
+    }
+
+    /// Token bucket: one token per `MIN_REQUEST_INTERVAL_MS`, holding up to
+    /// `burst_capacity`. A capacity of 1 spaces every request a full interval apart.
+    async fn enforce_rate_limit(&mut self) {
+        self.refill_tokens();
+        if self.tokens < 1.0 {
+            let wait = (1.0 - self.tokens) * MIN_REQUEST_INTERVAL_MS as f64;
+            tokio::time::sleep(std::time::Duration::from_millis(wait.ceil() as u64)).await;
+            self.refill_tokens();
         }
-        self.last_request_at = Some(Instant::now());
+        self.tokens = (self.tokens - 1.0).max(0.0);
     }
 
-    fn insert_memory_cache(&mut self, key: String, url: String) {
-        if self.memory_cache.len() >= MAX_MEMORY_ENTRIES {
+    fn refill_tokens(&mut self) {
+        let elapsed_ms = self.last_refill.elapsed().as_millis() as f64;
+        self.last_refill = Instant::now();
+        let refill = elapsed_ms / MIN_REQUEST_INTERVAL_MS as f64;
+        self.tokens = (self.tokens + refill).min(self.burst_capacity as f64);
+    }
+
+    fn insert_memory_cache(&mut self, key: String, url: String, explicit: bool) {
+        while self.memory_cache.len() >= self.max_memory_entries {
             // Evict oldest entry
-            if let Some(oldest_key) = self
+            let Some(oldest_key) = self
                 .memory_cache
                 .iter()
                 .min_by_key(|(_, v)| v.inserted_at)
                 .map(|(k, _)| k.clone())
-            {
-                self.memory_cache.remove(&oldest_key);
-            }
+            else {
+                break;
+            };
+            self.memory_cache.remove(&oldest_key);
         }
         self.memory_cache.insert(
             key,
             MemoryCacheEntry {
                 url,
                 inserted_at: Instant::now(),
+                explicit,
             },
         );
     }
 
-    fn insert_disk_cache(&mut self, key: String, url: String) {
+    fn insert_disk_cache(&mut self, key: String, url: String, explicit: bool) {
         self.disk_cache.entries.insert(
             key,
             DiskCacheEntry {
                 url,
                 fetched_at: now_unix_secs(),
+                explicit,
             },
         );
+        self.evict_disk_overflow();
         self.disk_cache_dirty = true;
     }
 
+    fn evict_disk_overflow(&mut self) {
+        while self.disk_cache.entries.len() > self.max_disk_entries {
+            // Evict the oldest fetch
+            let Some(oldest_key) = self
+                .disk_cache
+                .entries
+                .iter()
+                .min_by_key(|(_, v)| v.fetched_at)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            self.disk_cache.entries.remove(&oldest_key);
+        }
+    }
+
+    /// Write the disk cache to `path` in its on-disk format. Returns the entry count.
+    pub fn export_disk_cache(&self, path: &Path) -> Result<usize, String> {
+        let json = serde_json::to_string_pretty(&self.disk_cache)
+            .map_err(|e| format!("Failed to serialize art cache: {e}"))?;
+        std::fs::write(path, json)
+            .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+        Ok(self.disk_cache.entries.len())
+    }
+
+    /// Merge a cache written by `export_disk_cache` into the disk cache. Where both have
+    /// a key, the more recent fetch wins.
+    pub fn import_disk_cache(&mut self, path: &Path) -> Result<CacheImport, String> {
+        let data = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        let imported: DiskCache = serde_json::from_str(&data)
+            .map_err(|e| format!("Not an AMDP art cache export: {e}"))?;
+
+        let now = now_unix_secs();
+        let mut result = CacheImport {
+            added: 0,
+            updated: 0,
+            skipped: 0,
+        };
+        for (key, entry) in imported.entries {
+            let valid = key.contains("::")
+                && (entry.url.starts_with("https://") || entry.url.starts_with("http://"))
+                && now.saturating_sub(entry.fetched_at) < DISK_TTL_SECS;
+            if !valid {
+                result.skipped += 1;
+                continue;
+            }
+            match self.disk_cache.entries.get(&key) {
+                Some(existing) if existing.fetched_at >= entry.fetched_at => result.skipped += 1,
+                existing => {
+                    if existing.is_some() {
+                        result.updated += 1;
+                    } else {
+                        result.added += 1;
+                    }
+                    self.memory_cache.remove(&key);
+                    self.disk_cache.entries.insert(key, entry);
+                }
+            }
+        }
+
+        if result.added + result.updated > 0 {
+            self.evict_disk_overflow();
+            self.disk_cache_dirty = true;
+            self.save_disk_cache_if_dirty();
+        }
+        Ok(result)
+    }
+
     fn save_disk_cache_if_dirty(&mut self) {
         if !self.disk_cache_dirty {
             return;
@@ -268,3 +855,207 @@ impl AlbumArtResolver {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn album_tracks_share_a_key() {
+        assert_eq!(
+            cache_key("Radiohead", "OK Computer", "Airbag"),
+            cache_key("Radiohead", "OK Computer", "Karma Police")
+        );
+        assert_eq!(
+            cache_key("Radiohead", "OK Computer", "Airbag"),
+            "radiohead::ok computer"
+        );
+    }
+
+    #[test]
+    fn singles_are_keyed_by_track() {
+        assert_ne!(
+            cache_key("Artist", "", "First Single"),
+            cache_key("Artist", "", "Second Single")
+        );
+        assert_ne!(
+            cache_key("Artist", "First Single - Single", "First Single"),
+            cache_key("Artist", "Second Single - Single", "Second Single")
+        );
+        assert_eq!(
+            cache_key("Artist", "Song - Single", "Song"),
+            "artist::song - single::song"
+        );
+    }
+
+    #[test]
+    fn single_detection() {
+        assert!(is_single("", "Song"));
+        assert!(is_single("  ", "Song"));
+        assert!(is_single("Song", "song"));
+        assert!(is_single("Song - Single", "Song"));
+        assert!(!is_single("Album", "Song"));
+        assert!(!is_single("Other - Single", "Song"));
+    }
+
+    fn track_by(artist: &str, album_artist: Option<&str>) -> TrackInfo {
+        TrackInfo {
+            artist: artist.to_string(),
+            album_artist: album_artist.map(str::to_string),
+            ..crate::template::sample_track()
+        }
+    }
+
+    #[test]
+    fn query_prefers_album_artist() {
+        let track = track_by("Guest Artist", Some("Box Set Artist"));
+        assert_eq!(query_artist(&track), "Box Set Artist");
+        assert_eq!(track.artist, "Guest Artist");
+    }
+
+    #[test]
+    fn query_falls_back_to_track_artist() {
+        assert_eq!(
+            query_artist(&track_by("Track Artist", None)),
+            "Track Artist"
+        );
+        assert_eq!(
+            query_artist(&track_by("Track Artist", Some("  "))),
+            "Track Artist"
+        );
+    }
+
+    #[test]
+    fn search_term_with_and_without_album() {
+        let with_album = track_by("Guest Artist", Some("Box Set Artist"));
+        assert_eq!(
+            itunes_search_term(query_artist(&with_album), "The Complete Recordings"),
+            "Box Set Artist The Complete Recordings"
+        );
+        let without = track_by("Track Artist", None);
+        assert_eq!(
+            itunes_search_term(query_artist(&without), "  "),
+            "Track Artist"
+        );
+    }
+
+    #[test]
+    fn primary_artist_drops_featured_artists() {
+        assert_eq!(primary_artist("Artist A feat. Artist B"), "Artist A");
+        assert_eq!(primary_artist("Artist A (feat. Artist B)"), "Artist A");
+        assert_eq!(primary_artist("Artist A ft. Artist B"), "Artist A");
+        assert_eq!(primary_artist("Artist A FEATURING Artist B"), "Artist A");
+        assert_eq!(primary_artist("Featherweight"), "Featherweight");
+        assert_eq!(primary_artist("Artist A & Artist B"), "Artist A & Artist B");
+    }
+
+    #[test]
+    fn query_artist_is_primary_while_display_keeps_full_credit() {
+        let track = track_by("Artist A feat. Artist B", None);
+        assert_eq!(query_artist(&track), "Artist A");
+        assert_eq!(track.artist, "Artist A feat. Artist B");
+
+        let compilation = track_by("Guest", Some("Main Artist featuring Someone"));
+        assert_eq!(query_artist(&compilation), "Main Artist");
+    }
+
+    #[test]
+    fn decomposed_and_precomposed_names_share_a_key() {
+        let decomposed = "Beyonce\u{301}";
+        let precomposed = "Beyonc\u{e9}";
+        assert_ne!(decomposed, precomposed);
+        assert_eq!(
+            cache_key(decomposed, "Lemonade", "Formation"),
+            cache_key(precomposed, "Lemonade", "Formation")
+        );
+        assert_eq!(key_part("BEYONCE\u{301} "), "beyonc\u{e9}");
+    }
+
+    fn itunes_result(artist: &str, album: &str) -> ItunesResult {
+        ItunesResult {
+            collection_name: Some(album.to_string()),
+            artist_name: Some(artist.to_string()),
+            artwork_url_100: Some(format!(
+                "https://example.com/{artist}/{album}/100x100bb.jpg"
+            )),
+            collection_explicitness: None,
+        }
+    }
+
+    fn best_album(results: &[ItunesResult], artist: &str, album: &str) -> Option<String> {
+        best_itunes_match(results, artist, album).and_then(|r| r.collection_name.clone())
+    }
+
+    #[test]
+    fn similarity_compares_words() {
+        assert_eq!(similarity("The Beatles", "the beatles"), 1.0);
+        assert_eq!(similarity("Abbey Road", "Let It Be"), 0.0);
+        assert_eq!(similarity("Abbey Road", "Abbey Road (Remastered)"), 0.8);
+        assert_eq!(similarity("", ""), 1.0);
+    }
+
+    #[test]
+    fn ranking_prefers_the_closest_edition() {
+        let results = [
+            itunes_result("Artist", "Album (Deluxe Edition)"),
+            itunes_result("Artist", "Album"),
+        ];
+        assert_eq!(
+            best_album(&results, "Artist", "Album").as_deref(),
+            Some("Album")
+        );
+    }
+
+    #[test]
+    fn ranking_weighs_album_over_artist() {
+        let results = [
+            itunes_result("Artist", "Different Record"),
+            itunes_result("Tribute Band", "Album Title"),
+        ];
+        assert_eq!(
+            best_album(&results, "Artist", "Album Title").as_deref(),
+            Some("Album Title")
+        );
+    }
+
+    #[test]
+    fn ranking_uses_artist_when_albums_tie() {
+        let results = [
+            itunes_result("Tribute Band", "Greatest Hits"),
+            itunes_result("Real Artist", "Greatest Hits"),
+        ];
+        let best = best_itunes_match(&results, "Real Artist", "Greatest Hits").unwrap();
+        assert_eq!(best.artist_name.as_deref(), Some("Real Artist"));
+    }
+
+    #[test]
+    fn ranking_ties_keep_itunes_order() {
+        let results = [
+            itunes_result("Artist", "Album"),
+            itunes_result("Artist", "Album"),
+        ];
+        let best = best_itunes_match(&results, "Artist", "Album").unwrap();
+        assert!(std::ptr::eq(best, &results[0]));
+    }
+
+    #[test]
+    fn ranking_skips_results_without_artwork() {
+        let mut exact = itunes_result("Artist", "Album");
+        exact.artwork_url_100 = None;
+        let results = [exact, itunes_result("Artist", "Album (Live)")];
+        assert_eq!(
+            best_album(&results, "Artist", "Album").as_deref(),
+            Some("Album (Live)")
+        );
+        assert!(best_itunes_match(&[], "Artist", "Album").is_none());
+    }
+
+    #[test]
+    fn result_limit_is_at_least_one() {
+        let mut resolver = AlbumArtResolver::new();
+        resolver.set_itunes_result_limit(0);
+        assert_eq!(resolver.itunes_result_limit, 1);
+        resolver.set_itunes_result_limit(DEFAULT_ITUNES_RESULT_LIMIT);
+        assert_eq!(resolver.itunes_result_limit, DEFAULT_ITUNES_RESULT_LIMIT);
+    }
+}
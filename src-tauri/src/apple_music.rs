@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::source::MusicSource;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -11,75 +14,380 @@ pub struct TrackInfo {
     pub duration_secs: f64,
     pub position_secs: f64,
     pub is_playing: bool,
+    pub album_artist: Option<String>,
+    pub grouping: Option<String>,
+    /// 1-based position of the track within the current playlist, when known.
+    pub queue_index: Option<u32>,
+    pub queue_total: Option<u32>,
+    /// Sample rate in Hz, when Music.app reports one.
+    pub sample_rate: Option<u32>,
+    /// Bit rate in kbps, when Music.app reports one.
+    pub bit_rate: Option<u32>,
+    /// Name of the AirPlay device playback is sent to, when it isn't this Mac.
+    pub airplay_device: Option<String>,
+    /// Whole days since the track was added to the library.
+    pub added_days_ago: Option<u32>,
+    /// Name of the playlist playback is coming from, when Music.app reports one.
+    pub playlist_name: Option<String>,
+    /// Song and album ratings on Music.app's 0-100 scale (20 per star); unrated is `None`.
+    pub rating: Option<u8>,
+    pub album_rating: Option<u8>,
+    /// Times the track was skipped; 0 when never skipped or unknown.
+    #[serde(default)]
+    pub skip_count: u32,
+    /// Whole days since the track was last played to the end, `None` if never.
+    pub last_played_days_ago: Option<u32>,
+    /// The track's Comments field, collapsed onto one line.
+    pub comments: Option<String>,
+    #[serde(default)]
+    pub source_kind: TrackSourceKind,
+}
+
+/// Where the playing track comes from, as told by its AppleScript class and cloud status.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TrackSourceKind {
+    /// Apple Music or iTunes Store catalog, including local files matched to it.
+    #[default]
+    Catalog,
+    /// A file in the library that isn't in the catalog (rips, uploads, downloads).
+    Local,
+    /// An internet radio stream or other URL track.
+    Stream,
 }
 
 #[derive(Debug)]
 pub enum AppleMusicError {
     AppNotRunning,
+    NotPlaying,
     ScriptExecutionFailed(String),
     ParseError(String),
+    /// Another track read is still running; see `get_raw_track_output`.
+    Busy,
 }
 
 impl fmt::Display for AppleMusicError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             AppleMusicError::AppNotRunning => write!(f, "Music.app is not running"),
+            AppleMusicError::NotPlaying => write!(f, "Nothing is playing"),
             AppleMusicError::ScriptExecutionFailed(e) => write!(f, "AppleScript failed: {e}"),
             AppleMusicError::ParseError(e) => write!(f, "Parse error: {e}"),
+            AppleMusicError::Busy => write!(f, "A track read is already in progress"),
         }
     }
 }
 
+/// Process name of Music.app as seen by System Events.
+const MUSIC_PROCESS_NAME: &str = "Music";
+
+/// Quote `s` as an AppleScript string literal, escaping backslashes and double quotes.
+///
+/// Any value interpolated into a script (track, playlist or app names) must go through
+/// this; metadata containing `"` would otherwise end the literal early and let the rest
+/// of the name run as script.
+pub fn applescript_quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
 fn is_music_running() -> Result<bool, AppleMusicError> {
+    let script = format!(
+        "tell application \"System Events\" to (name of processes) contains {}",
+        applescript_quote(MUSIC_PROCESS_NAME)
+    );
     let output = Command::new("osascript")
         .arg("-e")
-        .arg(r#"tell application "System Events" to (name of processes) contains "Music""#)
+        .arg(script)
         .output()
         .map_err(|e| AppleMusicError::ScriptExecutionFailed(e.to_string()))?;
 
     let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    Ok(stdout == "true")
+    Ok(parse_bool(&stdout))
 }
 
-pub fn get_current_track() -> Result<TrackInfo, AppleMusicError> {
-    if !is_music_running()? {
-        return Err(AppleMusicError::AppNotRunning);
-    }
-
-    let script = r#"
+/// Reads the current track from Music.app.
+///
+/// Metadata comes from the application-level `current track` (never a window, so the
+/// mini player can't disagree with the main window). If that lookup fails while
+/// something is playing — typically an internet radio stream — the `current stream
+/// title` is used as the track name instead. Only when both are unavailable is the
+/// player treated as having nothing loaded.
+const TRACK_SCRIPT: &str = r#"
 tell application "Music"
     set playerState to player state as string
     if playerState is "stopped" then
         return "stopped||||||"
     end if
-    set trackName to name of current track
-    set trackArtist to artist of current track
-    set trackAlbum to album of current track
-    set trackDuration to duration of current track
+    if playerState is "playing" then
+        set isPlaying to "true"
+    else
+        set isPlaying to "false"
+    end if
     set trackPosition to player position
-    set isPlaying to (playerState is "playing")
-    return trackName & "||" & trackArtist & "||" & trackAlbum & "||" & trackDuration & "||" & trackPosition & "||" & isPlaying
+    set airplayDevice to ""
+    try
+        repeat with theDevice in (current AirPlay devices)
+            if kind of theDevice is not computer then
+                set airplayDevice to name of theDevice
+                exit repeat
+            end if
+        end repeat
+    end try
+    try
+        set theTrack to current track
+        set trackName to name of theTrack
+        set trackArtist to artist of theTrack
+        set trackAlbum to album of theTrack
+        set trackDuration to duration of theTrack
+        set trackStart to ""
+        set trackFinish to ""
+        try
+            set trackStart to start of theTrack
+            set trackFinish to finish of theTrack
+        end try
+        set trackAlbumArtist to ""
+        set trackGrouping to ""
+        try
+            set trackAlbumArtist to album artist of theTrack
+            set trackGrouping to grouping of theTrack
+        end try
+        set queueIndex to ""
+        set queueTotal to ""
+        try
+            set queueIndex to index of theTrack
+            set queueTotal to count of tracks of current playlist
+        end try
+        set playlistName to ""
+        try
+            set playlistName to name of current playlist
+        end try
+        set sampleRate to ""
+        set bitRate to ""
+        try
+            set sampleRate to sample rate of theTrack
+            set bitRate to bit rate of theTrack
+        end try
+        -- Age in days rather than the date itself, which is formatted per locale
+        set addedDaysAgo to ""
+        try
+            set addedDaysAgo to ((current date) - (date added of theTrack)) div days
+        end try
+        set trackRating to ""
+        set albumRating to ""
+        try
+            set trackRating to rating of theTrack
+            set albumRating to album rating of theTrack
+        end try
+        set skipCount to ""
+        try
+            set skipCount to skipped count of theTrack
+        end try
+        set lastPlayedDaysAgo to ""
+        try
+            set lastPlayedDaysAgo to ((current date) - (played date of theTrack)) div days
+        end try
+        set trackComments to ""
+        try
+            set trackComments to comment of theTrack
+        end try
+        -- Class and cloud status names are script keywords, so these don't vary by locale
+        set sourceKind to "catalog"
+        try
+            set trackClass to (class of theTrack) as string
+            if trackClass is "URL track" then
+                set sourceKind to "stream"
+            else if trackClass is "file track" then
+                set cloudStatus to (cloud status of theTrack) as string
+                if cloudStatus is not in {"purchased", "matched", "subscription"} then
+                    set sourceKind to "local"
+                end if
+            end if
+        end try
+    on error
+        set streamTitle to missing value
+        try
+            set streamTitle to current stream title
+        end try
+        if streamTitle is missing value or streamTitle is "" then
+            return "stopped||||||"
+        end if
+        set trackName to streamTitle
+        set trackArtist to ""
+        set trackAlbum to ""
+        set trackDuration to 0
+        set trackAlbumArtist to ""
+        set trackGrouping to ""
+        set queueIndex to ""
+        set queueTotal to ""
+        set sampleRate to ""
+        set bitRate to ""
+        set trackStart to ""
+        set trackFinish to ""
+        set addedDaysAgo to ""
+        set playlistName to ""
+        set trackRating to ""
+        set albumRating to ""
+        set skipCount to ""
+        set lastPlayedDaysAgo to ""
+        set trackComments to ""
+        set sourceKind to "stream"
+    end try
+    return trackName & "||" & trackArtist & "||" & trackAlbum & "||" & trackDuration & "||" & trackPosition & "||" & isPlaying & "||" & trackAlbumArtist & "||" & trackGrouping & "||" & queueIndex & "||" & queueTotal & "||" & sampleRate & "||" & bitRate & "||" & trackStart & "||" & trackFinish & "||" & airplayDevice & "||" & addedDaysAgo & "||" & playlistName & "||" & trackRating & "||" & albumRating & "||" & skipCount & "||" & lastPlayedDaysAgo & "||" & sourceKind & "||" & trackComments
 end tell
 "#;
 
+/// Bring Music.app to the front with the current track selected.
+pub fn reveal_current_track() -> Result<(), AppleMusicError> {
     let output = Command::new("osascript")
         .arg("-e")
-        .arg(script)
+        .arg("tell application \"Music\"\nactivate\nreveal current track\nend tell")
         .output()
         .map_err(|e| AppleMusicError::ScriptExecutionFailed(e.to_string()))?;
-
     if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        return Err(AppleMusicError::ScriptExecutionFailed(stderr));
+        return Err(AppleMusicError::ScriptExecutionFailed(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
     }
+    Ok(())
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    parse_track_response(&stdout)
+/// Unparsed result of running the track AppleScript.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawTrackOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub success: bool,
+}
+
+/// Set while a track read is running, so reads never overlap.
+static TRACK_READ_IN_FLIGHT: AtomicBool = AtomicBool::new(false);
+
+/// Clears `TRACK_READ_IN_FLIGHT` when the read finishes, however it returns.
+struct InFlightGuard;
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        TRACK_READ_IN_FLIGHT.store(false, Ordering::Release);
+    }
+}
+
+/// Run the track AppleScript and return its output without parsing. Fails with `Busy`
+/// rather than spawning a second `osascript` while a read is still running.
+pub fn get_raw_track_output() -> Result<RawTrackOutput, AppleMusicError> {
+    if TRACK_READ_IN_FLIGHT.swap(true, Ordering::Acquire) {
+        return Err(AppleMusicError::Busy);
+    }
+    let _guard = InFlightGuard;
+
+    if !is_music_running()? {
+        return Err(AppleMusicError::AppNotRunning);
+    }
+
+    let output = Command::new("osascript")
+        .arg("-e")
+        .arg(TRACK_SCRIPT)
+        .output()
+        .map_err(|e| AppleMusicError::ScriptExecutionFailed(e.to_string()))?;
+
+    Ok(RawTrackOutput {
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        success: output.status.success(),
+    })
+}
+
+/// Music.app, read over AppleScript.
+pub struct AppleMusicSource;
+
+impl MusicSource for AppleMusicSource {
+    fn name(&self) -> &'static str {
+        "Apple Music"
+    }
+
+    fn current_track(&self) -> Result<TrackInfo, AppleMusicError> {
+        get_current_track()
+    }
+}
+
+pub fn get_current_track() -> Result<TrackInfo, AppleMusicError> {
+    let output = get_raw_track_output()?;
+
+    if !output.success {
+        return Err(AppleMusicError::ScriptExecutionFailed(output.stderr));
+    }
+
+    parse_track_response(output.stdout.trim())
+}
+
+/// Parse a seconds field. Streams report `missing value` (or nothing) for duration,
+/// which is treated as zero.
+fn parse_seconds(field: &str) -> Result<f64, std::num::ParseFloatError> {
+    let field = field.trim();
+    if field.is_empty() || field == "missing value" {
+        return Ok(0.0);
+    }
+    field.parse::<f64>()
+}
+
+/// Parse an AppleScript boolean. The scripts emit `true`/`false`, but some locales and
+/// setups render booleans differently, so `yes` and `1` are accepted too, in any case.
+fn parse_bool(field: &str) -> bool {
+    let field = field.trim();
+    ["true", "yes", "1"].iter().any(|t| field.eq_ignore_ascii_case(t))
+}
+
+/// Parse an optional text field; empty strings and `missing value` become `None`.
+fn parse_optional_text(field: Option<&str>) -> Option<String> {
+    let field = field?.trim();
+    if field.is_empty() || field == "missing value" {
+        return None;
+    }
+    Some(field.to_string())
+}
+
+/// Parse an optional positive count; zero, blanks and junk become `None`.
+fn parse_optional_count(field: Option<&str>) -> Option<u32> {
+    field?.trim().parse::<u32>().ok().filter(|n| *n > 0)
+}
+
+/// Parse a 0-100 rating; 0 (unrated), blanks and out-of-range values become `None`.
+fn parse_rating(field: Option<&str>) -> Option<u8> {
+    field?.trim().parse::<u8>().ok().filter(|r| (1..=100).contains(r))
+}
+
+/// Custom start/stop times set on the track in Music.app, as `(start, finish)` seconds.
+/// Returns `None` for untrimmed tracks (start 0, finish at the full duration) and for
+/// missing or inconsistent values.
+fn parse_trim(start: Option<&str>, finish: Option<&str>, duration_secs: f64) -> Option<(f64, f64)> {
+    let start = parse_seconds(start?).ok()?;
+    let finish = parse_seconds(finish?).ok()?;
+    if finish <= start || finish > duration_secs + 0.5 {
+        return None;
+    }
+    // Sub-second differences are rounding, not a trim
+    let trimmed = start >= 0.5 || duration_secs - finish >= 0.5;
+    trimmed.then_some((start, finish))
 }
 
 fn parse_track_response(response: &str) -> Result<TrackInfo, AppleMusicError> {
     let parts: Vec<&str> = response.split("||").collect();
 
+    // The script's "stopped" marker is shorter than a track line
+    if parts[0] == "stopped" {
+        return Err(AppleMusicError::NotPlaying);
+    }
+
     if parts.len() < 6 {
         return Err(AppleMusicError::ParseError(format!(
             "Expected 6 fields, got {}: {response}",
@@ -87,19 +395,53 @@ fn parse_track_response(response: &str) -> Result<TrackInfo, AppleMusicError> {
         )));
     }
 
-    if parts[0] == "stopped" {
-        return Err(AppleMusicError::AppNotRunning);
-    }
-
-    let duration_secs = parts[3]
-        .parse::<f64>()
+    let duration_secs = parse_seconds(parts[3])
         .map_err(|e| AppleMusicError::ParseError(format!("Invalid duration: {e}")))?;
 
-    let position_secs = parts[4]
-        .parse::<f64>()
+    let position_secs = parse_seconds(parts[4])
         .map_err(|e| AppleMusicError::ParseError(format!("Invalid position: {e}")))?;
 
-    let is_playing = parts[5] == "true";
+    let trim = parse_trim(parts.get(12).copied(), parts.get(13).copied(), duration_secs);
+    let (duration_secs, position_secs) = match trim {
+        Some((start, finish)) => (finish - start, position_secs - start),
+        None => (duration_secs, position_secs),
+    };
+    // Reads right at a track boundary can land slightly before the start or past the end;
+    // left alone they push Discord's start timestamp into the future or past the end
+    let position_secs = if duration_secs > 0.0 {
+        position_secs.clamp(0.0, duration_secs)
+    } else {
+        position_secs.max(0.0)
+    };
+
+    let is_playing = parse_bool(parts[5]);
+    let album_artist = parse_optional_text(parts.get(6).copied());
+    let grouping = parse_optional_text(parts.get(7).copied());
+    let queue_index = parse_optional_count(parts.get(8).copied());
+    let queue_total = parse_optional_count(parts.get(9).copied());
+    let sample_rate = parse_optional_count(parts.get(10).copied());
+    let bit_rate = parse_optional_count(parts.get(11).copied());
+    let airplay_device = parse_optional_text(parts.get(14).copied());
+    let added_days_ago = parts.get(15).and_then(|f| f.trim().parse::<u32>().ok());
+    let playlist_name = parse_optional_text(parts.get(16).copied());
+    let rating = parse_rating(parts.get(17).copied());
+    let album_rating = parse_rating(parts.get(18).copied());
+    let skip_count = parts
+        .get(19)
+        .and_then(|f| f.trim().parse::<u32>().ok())
+        .unwrap_or(0);
+    // A never-played track has a missing played date, which leaves the field blank
+    let last_played_days_ago = parts.get(20).and_then(|f| f.trim().parse::<u32>().ok());
+    let source_kind = match parts.get(21).map(|f| f.trim()) {
+        Some("local") => TrackSourceKind::Local,
+        Some("stream") => TrackSourceKind::Stream,
+        _ => TrackSourceKind::Catalog,
+    };
+    // Comments come last and are free text, so any "||" or line breaks in them are theirs
+    let comments = parts.get(22..).and_then(|rest| {
+        let joined = rest.join("||");
+        parse_optional_text(Some(&joined.split_whitespace().collect::<Vec<_>>().join(" ")))
+    });
 
     Ok(TrackInfo {
         name: parts[0].to_string(),
@@ -108,5 +450,189 @@ fn parse_track_response(response: &str) -> Result<TrackInfo, AppleMusicError> {
         duration_secs,
         position_secs,
         is_playing,
+        album_artist,
+        grouping,
+        queue_index,
+        queue_total,
+        sample_rate,
+        bit_rate,
+        airplay_device,
+        added_days_ago,
+        playlist_name,
+        rating,
+        album_rating,
+        skip_count,
+        last_played_days_ago,
+        comments,
+        source_kind,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `TRACK_SCRIPT` line for a catalog track with every optional field blank, with
+    /// `overrides` applied by field index.
+    fn response(overrides: &[(usize, &str)]) -> String {
+        let mut fields = vec![""; 23];
+        fields[..6].copy_from_slice(&["Song", "Artist", "Album", "240", "30", "true"]);
+        fields[21] = "catalog";
+        for &(index, value) in overrides {
+            fields[index] = value;
+        }
+        fields.join("||")
+    }
+
+    #[test]
+    fn stopped_player_is_not_playing() {
+        assert!(matches!(
+            parse_track_response("stopped||||||"),
+            Err(AppleMusicError::NotPlaying)
+        ));
+    }
+
+    #[test]
+    fn stream_title_only_response() {
+        let track = parse_track_response(&response(&[
+            (0, "Morning Show - Live"),
+            (1, ""),
+            (2, ""),
+            (3, "0"),
+            (4, "1234.5"),
+            (21, "stream"),
+        ]))
+        .unwrap();
+        assert_eq!(track.name, "Morning Show - Live");
+        assert_eq!(track.artist, "");
+        assert_eq!(track.album, "");
+        assert_eq!(track.duration_secs, 0.0);
+        assert_eq!(track.position_secs, 1234.5);
+        assert!(track.is_playing);
+        assert_eq!(track.source_kind, TrackSourceKind::Stream);
+    }
+
+    #[test]
+    fn stream_duration_of_missing_value_is_zero() {
+        let track = parse_track_response(&response(&[(3, "missing value")])).unwrap();
+        assert_eq!(track.duration_secs, 0.0);
+    }
+
+    #[test]
+    fn quotes_plain_names() {
+        assert_eq!(applescript_quote("Song"), r#""Song""#);
+        assert_eq!(applescript_quote(""), r#""""#);
+    }
+
+    #[test]
+    fn escapes_quotes_and_backslashes() {
+        assert_eq!(applescript_quote(r#"Say "Hi""#), r#""Say \"Hi\"""#);
+        assert_eq!(applescript_quote(r"AC\DC"), r#""AC\\DC""#);
+        assert_eq!(applescript_quote(r#"\""#), r#""\\\"""#);
+    }
+
+    #[test]
+    fn adversarial_name_stays_one_literal() {
+        let name = r#"x" & (do shell script "touch /tmp/pwned") & ""#;
+        assert_eq!(
+            applescript_quote(name),
+            r#""x\" & (do shell script \"touch /tmp/pwned\") & \"""#
+        );
+    }
+
+    #[test]
+    fn trimmed_track_uses_start_and_finish() {
+        let track = parse_track_response(&response(&[
+            (3, "300"),
+            (4, "100"),
+            (12, "30"),
+            (13, "270"),
+        ]))
+        .unwrap();
+        assert_eq!(track.duration_secs, 240.0);
+        assert_eq!(track.position_secs, 70.0);
+    }
+
+    #[test]
+    fn untrimmed_track_keeps_full_duration() {
+        let track =
+            parse_track_response(&response(&[(3, "300"), (4, "100"), (12, "0"), (13, "300")]))
+                .unwrap();
+        assert_eq!(track.duration_secs, 300.0);
+        assert_eq!(track.position_secs, 100.0);
+    }
+
+    #[test]
+    fn inconsistent_trim_is_ignored() {
+        assert_eq!(parse_trim(Some("200"), Some("100"), 300.0), None);
+        assert_eq!(parse_trim(Some("0"), Some("400"), 300.0), None);
+        assert_eq!(parse_trim(Some(""), Some(""), 300.0), None);
+        assert_eq!(parse_trim(None, None, 300.0), None);
+    }
+
+    #[test]
+    fn negative_position_clamps_to_zero() {
+        let track = parse_track_response(&response(&[(3, "240"), (4, "-0.4")])).unwrap();
+        assert_eq!(track.position_secs, 0.0);
+    }
+
+    #[test]
+    fn position_past_the_end_clamps_to_duration() {
+        let track = parse_track_response(&response(&[(3, "240"), (4, "241.7")])).unwrap();
+        assert_eq!(track.position_secs, 240.0);
+    }
+
+    #[test]
+    fn position_before_trim_start_clamps_to_zero() {
+        let track =
+            parse_track_response(&response(&[(3, "300"), (4, "10"), (12, "30"), (13, "270")]))
+                .unwrap();
+        assert_eq!(track.position_secs, 0.0);
+    }
+
+    #[test]
+    fn stream_position_is_only_floored() {
+        let track = parse_track_response(&response(&[(3, "0"), (4, "-1")])).unwrap();
+        assert_eq!(track.position_secs, 0.0);
+        let track = parse_track_response(&response(&[(3, "0"), (4, "5000")])).unwrap();
+        assert_eq!(track.position_secs, 5000.0);
+    }
+
+    #[test]
+    fn booleans_in_any_case_and_form() {
+        for truthy in ["true", "TRUE", "True", "yes", "Yes", "1", " true\n"] {
+            assert!(parse_bool(truthy), "{truthy:?}");
+        }
+        for falsy in ["false", "FALSE", "no", "0", "", "missing value", "truthy"] {
+            assert!(!parse_bool(falsy), "{falsy:?}");
+        }
+    }
+
+    #[test]
+    fn playing_state_accepts_alternate_booleans() {
+        let playing = parse_track_response(&response(&[(5, "YES")])).unwrap();
+        assert!(playing.is_playing);
+        let paused = parse_track_response(&response(&[(5, "False")])).unwrap();
+        assert!(!paused.is_playing);
+    }
+
+    #[test]
+    fn source_kind_for_each_marker() {
+        for (marker, kind) in [
+            ("catalog", TrackSourceKind::Catalog),
+            ("local", TrackSourceKind::Local),
+            ("stream", TrackSourceKind::Stream),
+        ] {
+            let track = parse_track_response(&response(&[(21, marker)])).unwrap();
+            assert_eq!(track.source_kind, kind, "{marker}");
+        }
+    }
+
+    #[test]
+    fn source_kind_defaults_to_catalog() {
+        let track = parse_track_response(&response(&[(21, "")])).unwrap();
+        assert_eq!(track.source_kind, TrackSourceKind::Catalog);
+        let short = parse_track_response("Song||Artist||Album||240||30||true").unwrap();
+        assert_eq!(short.source_kind, TrackSourceKind::Catalog);
+    }
+}
@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+/// Where copied text ended up.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum CopyOutcome {
+    Copied,
+    /// The clipboard was unavailable; the text was written to `path` instead.
+    SavedToFile { path: String },
+}
+
+fn fallback_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".amdp")
+        .join("last-copied.txt")
+}
+
+/// Copy `text` to the clipboard, falling back to `~/.amdp/last-copied.txt` when the
+/// clipboard can't be accessed (e.g. under some permission states).
+pub fn copy_text(text: &str) -> Result<CopyOutcome, String> {
+    let clipboard_err = match arboard::Clipboard::new() {
+        Ok(mut clipboard) => match clipboard.set_text(text) {
+            Ok(()) => return Ok(CopyOutcome::Copied),
+            Err(e) => format!("Failed to copy to clipboard: {e}"),
+        },
+        Err(e) => format!("Failed to access clipboard: {e}"),
+    };
+    tracing::warn!("{clipboard_err}; writing to file instead");
+
+    let path = fallback_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("{clipboard_err}; failed to create {}: {e}", parent.display()))?;
+    }
+    std::fs::write(&path, text)
+        .map_err(|e| format!("{clipboard_err}; failed to write {}: {e}", path.display()))?;
+    Ok(CopyOutcome::SavedToFile {
+        path: path.display().to_string(),
+    })
+}
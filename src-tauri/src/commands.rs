@@ -1,10 +1,106 @@
+use std::sync::atomic::Ordering;
+
+use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Emitter, State};
 use tauri_plugin_autostart::ManagerExt;
 
-use crate::apple_music::TrackInfo;
-use crate::config::{self, AppConfig};
-use crate::discord_rpc::DiscordStatus;
-use crate::state::AppState;
+use crate::album_art::{self, CacheImport, CacheStats};
+use crate::apple_music::{self, RawTrackOutput, TrackInfo};
+use crate::clipboard::{self, CopyOutcome};
+use crate::config::{self, AppConfig, DisplayFormat};
+use crate::discord_rpc::{self, AssetCheck, DiscordStatus, StatusChange};
+use crate::state::{AppState, EffectivePollInterval};
+use crate::template;
+use crate::tray;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CopyFormat {
+    Plain,
+    Markdown,
+}
+
+fn escape_markdown(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '*' | '_' | '[' | ']' | '`') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+pub fn format_now_playing(track: &TrackInfo, artwork_url: Option<&str>, format: CopyFormat) -> String {
+    match format {
+        CopyFormat::Plain => format!("{} \u{2014} {}", track.name, track.artist),
+        CopyFormat::Markdown => {
+            let mut text = format!(
+                "**{}** by *{}*",
+                escape_markdown(&track.name),
+                escape_markdown(&track.artist)
+            );
+            if !track.album.trim().is_empty() {
+                text.push_str(&format!(" \u{2014} {}", escape_markdown(&track.album)));
+            }
+            if let Some(url) = artwork_url {
+                text.push_str(&format!(" \u{2014} [cover]({url})"));
+            }
+            text
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateStatus {
+    pub available: bool,
+    pub version: Option<String>,
+    pub installing: bool,
+    pub last_check_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostics {
+    pub note: &'static str,
+    pub app_version: &'static str,
+    pub macos_version: Option<String>,
+    pub safe_mode: bool,
+    pub config: AppConfig,
+    pub discord_status: DiscordStatus,
+    pub discord_status_history: Vec<StatusChange>,
+    pub current_track: Option<TrackInfo>,
+    pub art_cache: CacheStats,
+}
+
+fn macos_version() -> Option<String> {
+    let output = std::process::Command::new("sw_vers")
+        .arg("-productVersion")
+        .output()
+        .ok()?;
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!version.is_empty()).then_some(version)
+}
+
+async fn collect_diagnostics(state: &AppState) -> Diagnostics {
+    // Await the resolver first so no std mutex guard is held across the await
+    let art_cache = state.art_resolver.lock().await.stats();
+    let config = state.config.lock().unwrap().clone();
+    let current_track = state.current_track.lock().unwrap().clone();
+
+    Diagnostics {
+        note: "AMDP diagnostics \u{2014} paste this into your GitHub issue",
+        app_version: env!("CARGO_PKG_VERSION"),
+        macos_version: macos_version(),
+        safe_mode: state.safe_mode,
+        config,
+        discord_status: state.discord.get_status(),
+        discord_status_history: state.discord.get_status_history(),
+        current_track,
+        art_cache,
+    }
+}
 
 #[tauri::command]
 pub fn get_current_track(state: State<AppState>) -> Option<TrackInfo> {
@@ -16,19 +112,117 @@ pub fn get_discord_status(state: State<AppState>) -> DiscordStatus {
     state.discord.get_status()
 }
 
+/// The interval the poll loop is currently sleeping for, and why.
+#[tauri::command]
+pub fn get_effective_poll_interval(state: State<AppState>) -> Option<EffectivePollInterval> {
+    state.effective_poll_interval.lock().unwrap().clone()
+}
+
+/// Resume reconnecting to Discord after `max_reconnect_attempts` gave up.
+#[tauri::command]
+pub fn reconnect_discord(state: State<AppState>) {
+    tracing::info!("Discord reconnect requested");
+    state.discord.reconnect();
+}
+
+/// Check that the Discord app has every asset key the presence refers to.
+#[tauri::command]
+pub async fn verify_discord_assets(state: State<'_, AppState>) -> Result<AssetCheck, String> {
+    if state.safe_mode {
+        return Err("Discord is disabled in safe mode".to_string());
+    }
+    let small_image_mode = state.config.lock().unwrap().small_image_mode;
+    let check = discord_rpc::verify_assets(small_image_mode).await?;
+    if !check.missing.is_empty() {
+        tracing::warn!("Discord app {} is missing assets: {:?}", check.app_id, check.missing);
+    }
+    Ok(check)
+}
+
 #[tauri::command]
 pub fn get_config(state: State<AppState>) -> AppConfig {
     state.config.lock().unwrap().clone()
 }
 
+/// An http(s) URL, or something shaped like a Discord asset key.
+fn is_plausible_image(image: &str) -> bool {
+    if let Some(rest) = image
+        .strip_prefix("https://")
+        .or_else(|| image.strip_prefix("http://"))
+    {
+        return !rest.is_empty() && !rest.contains(char::is_whitespace);
+    }
+    image.len() <= 256
+        && image
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
+
 #[tauri::command]
-pub fn save_config(
+pub async fn save_config(
     app: AppHandle,
-    state: State<AppState>,
+    state: State<'_, AppState>,
     mut new_config: AppConfig,
-) -> Result<(), String> {
+) -> Result<AppConfig, String> {
     // Clamp poll interval to valid range
     new_config.poll_interval_secs = new_config.poll_interval_secs.clamp(2, 15);
+    new_config.poll_interval_playing_secs = new_config.poll_interval_playing_secs.map(|s| s.clamp(2, 15));
+    new_config.poll_interval_paused_secs = new_config.poll_interval_paused_secs.map(|s| s.clamp(2, 60));
+
+    new_config.wake_detection_slack_secs = new_config.wake_detection_slack_secs.clamp(1, 300);
+    new_config.relaunch_delay_secs = new_config.relaunch_delay_secs.clamp(1, 30);
+    new_config.track_event_min_interval_secs = new_config.track_event_min_interval_secs.min(60);
+    new_config.art_concurrency = new_config.art_concurrency.clamp(1, 8);
+    new_config.itunes_result_limit = new_config.itunes_result_limit.clamp(1, 25);
+    new_config.presence_heartbeat_secs = new_config
+        .presence_heartbeat_secs
+        .filter(|s| *s > 0)
+        .map(|s| s.clamp(20, 900));
+    new_config.session_gap_secs = new_config.session_gap_secs.clamp(60, 6 * 3600);
+    new_config.max_reconnect_attempts = new_config.max_reconnect_attempts.filter(|n| *n > 0);
+    new_config.auto_stop_after_hours = new_config.auto_stop_after_hours.filter(|h| *h > 0);
+
+    // Keep art caches large enough to be useful
+    new_config.max_memory_art_entries = new_config.max_memory_art_entries.max(50);
+    new_config.max_disk_art_entries = new_config.max_disk_art_entries.max(100);
+
+    // Periodic update checks no more than hourly
+    if new_config.update_check_interval_secs > 0 {
+        new_config.update_check_interval_secs = new_config.update_check_interval_secs.max(3600);
+    }
+    new_config.update_check_retries = new_config.update_check_retries.min(5);
+    if new_config.update_failure_recheck_secs > 0 {
+        new_config.update_failure_recheck_secs = new_config.update_failure_recheck_secs.max(300);
+    }
+
+    new_config.fixed_large_image_url = new_config
+        .fixed_large_image_url
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+    if let Some(image) = &new_config.fixed_large_image_url {
+        if !is_plausible_image(image) {
+            return Err(format!(
+                "Fixed large image must be an http(s) URL or a Discord asset key: {image}"
+            ));
+        }
+    }
+
+    // Discord rejects details shorter than two characters
+    new_config.idle_presence = new_config
+        .idle_presence
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+    if new_config
+        .idle_presence
+        .as_ref()
+        .is_some_and(|s| s.chars().count() < 2)
+    {
+        return Err("Idle presence text must be at least 2 characters".to_string());
+    }
+
+    if new_config.display_format == DisplayFormat::Custom {
+        template::validate_templates(&new_config)?;
+    }
 
     // Detect launch_at_login change
     let old_launch_at_login = {
@@ -45,17 +239,28 @@ pub fn save_config(
         }
     }
 
-    // Detect enable_on_launch change for tray sync
-    let old_enabled = {
+    // Detect enable_on_launch, display_format and menu bar title changes for tray sync
+    let (old_enabled, old_display_format, old_show_track_in_menubar) = {
         let cfg = state.config.lock().unwrap();
-        cfg.enable_on_launch
+        (cfg.enable_on_launch, cfg.display_format, cfg.show_track_in_menubar)
     };
 
+    if old_enabled && !new_config.enable_on_launch {
+        state.set_presence_gate(false);
+    }
+
     // Write to state
     {
         let mut cfg = state.config.lock().unwrap();
         *cfg = new_config.clone();
     }
+    state
+        .discord
+        .set_heartbeat_interval(new_config.presence_heartbeat_secs);
+    state
+        .discord
+        .set_max_reconnect_attempts(new_config.max_reconnect_attempts);
+    crate::configure_art_resolver(&mut *state.art_resolver.lock().await, &new_config);
 
     // Persist to disk
     tracing::info!("Saving config to disk");
@@ -68,11 +273,436 @@ pub fn save_config(
         }
     }
 
-    // If presence disabled, clear Discord
-    if !new_config.enable_on_launch {
+    tray::set_custom_format_enabled(&state, new_config.has_custom_templates());
+    if new_config.display_format != old_display_format {
+        tray::sync_display_format_items(&state, new_config.display_format);
+        state.force_resync.store(true, Ordering::Relaxed);
+    }
+
+    if new_config.show_track_in_menubar != old_show_track_in_menubar {
+        let current = state.current_track.lock().unwrap().clone();
+        crate::update_now_playing_label(&app, &current, &new_config);
+    }
+
+    if new_config.enable_on_launch && !old_enabled {
+        state.set_presence_gate(true);
+    } else if !new_config.enable_on_launch {
+        // Presence disabled; clear Discord
         state.discord.clear_presence();
     }
 
     let _ = app.emit("config-changed", ());
+    Ok(new_config)
+}
+
+/// Text produced by `copy_now_playing` and where it was copied to.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NowPlayingCopy {
+    pub text: String,
+    pub outcome: CopyOutcome,
+}
+
+#[tauri::command]
+pub fn copy_now_playing(
+    state: State<AppState>,
+    format: CopyFormat,
+) -> Result<NowPlayingCopy, String> {
+    let track = state
+        .current_track
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| "Nothing is playing".to_string())?;
+    let artwork_url = state.current_artwork_url.lock().unwrap().clone();
+    let text = format_now_playing(&track, artwork_url.as_deref(), format);
+
+    let outcome = clipboard::copy_text(&text)?;
+    tracing::info!("Copied now playing ({format:?}, {outcome:?})");
+    Ok(NowPlayingCopy { text, outcome })
+}
+
+#[tauri::command]
+pub async fn get_cache_stats(state: State<'_, AppState>) -> Result<CacheStats, String> {
+    Ok(state.art_resolver.lock().await.stats())
+}
+
+/// Write the album art cache to `path` for backup or moving to another Mac. Only the
+/// cached URLs are exported, not the images. Returns the number of entries written.
+#[tauri::command]
+pub async fn export_art_cache(path: String, state: State<'_, AppState>) -> Result<usize, String> {
+    let count = state
+        .art_resolver
+        .lock()
+        .await
+        .export_disk_cache(std::path::Path::new(&path))?;
+    tracing::info!("Exported {count} art cache entries to {path}");
+    Ok(count)
+}
+
+/// Merge an exported art cache into this one; newer fetches win on conflicts.
+#[tauri::command]
+pub async fn import_art_cache(
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<CacheImport, String> {
+    let result = state
+        .art_resolver
+        .lock()
+        .await
+        .import_disk_cache(std::path::Path::new(&path))?;
+    tracing::info!("Imported art cache from {path}: {result:?}");
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn get_raw_track_output() -> Result<RawTrackOutput, String> {
+    tokio::task::spawn_blocking(apple_music::get_raw_track_output)
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_update_status(state: State<AppState>) -> UpdateStatus {
+    let version = state.update_available.lock().unwrap().clone();
+    UpdateStatus {
+        available: version.is_some(),
+        version,
+        installing: state.update_installing.load(Ordering::Relaxed),
+        last_check_error: state.update_check_error.lock().unwrap().clone(),
+    }
+}
+
+#[tauri::command]
+pub fn get_track_history(state: State<AppState>) -> Vec<TrackInfo> {
+    state.track_history.lock().unwrap().iter().cloned().collect()
+}
+
+/// Show a track from history as a paused presence until real playback resumes.
+#[tauri::command]
+pub async fn set_presence_from_history(
+    state: State<'_, AppState>,
+    index: usize,
+) -> Result<TrackInfo, String> {
+    let mut track = state
+        .track_history
+        .lock()
+        .unwrap()
+        .get(index)
+        .cloned()
+        .ok_or_else(|| format!("No history entry at index {index}"))?;
+    track.is_playing = false;
+
+    let cfg = state.config.lock().unwrap().clone();
+    let artwork_url = if cfg.album_art_enabled() {
+        state
+            .art_resolver
+            .lock()
+            .await
+            .resolve(album_art::query_artist(&track), &track.album, &track.name)
+            .await
+    } else {
+        None
+    };
+
+    let now = discord_rpc::now_secs();
+    let opts = crate::build_activity_options(&cfg, now, now);
+    // Goes through the presence gate like the poll loop, so it can't show while disabled
+    let pushed = state.push_presence(|discord| {
+        discord.set_paused(&track, artwork_url, opts);
+        state.manual_override.store(true, Ordering::Relaxed);
+    });
+    if !pushed {
+        return Err("Presence is turned off".to_string());
+    }
+    tracing::info!("Manual presence override from history: \"{}\"", track.name);
+    Ok(track)
+}
+
+#[tauri::command]
+pub async fn dump_diagnostics(state: State<'_, AppState>) -> Result<Diagnostics, String> {
+    Ok(collect_diagnostics(&state).await)
+}
+
+/// Copy the diagnostics snapshot to the clipboard as pretty-printed JSON.
+#[tauri::command]
+pub async fn copy_diagnostics(state: State<'_, AppState>) -> Result<CopyOutcome, String> {
+    let diagnostics = collect_diagnostics(&state).await;
+    let json = serde_json::to_string_pretty(&diagnostics)
+        .map_err(|e| format!("Failed to serialize diagnostics: {e}"))?;
+
+    let outcome = clipboard::copy_text(&json)?;
+    tracing::info!("Copied diagnostics ({outcome:?})");
+    Ok(outcome)
+}
+
+/// Pause or resume the poll loop without touching the Discord connection.
+#[tauri::command]
+pub fn set_polling_enabled(state: State<'_, AppState>, enabled: bool) {
+    tracing::info!("Polling {}", if enabled { "resumed" } else { "paused" });
+    state.polling_enabled.store(enabled, Ordering::Relaxed);
+    if let Some(item) = state.polling_item.lock().unwrap().as_ref() {
+        let _ = item.set_checked(enabled);
+    }
+}
+
+/// Check a display template and render it against a sample track for a live preview.
+#[tauri::command]
+pub fn validate_template(template: String) -> Result<template::Rendered, String> {
+    template::render(&template, &template::sample_track())
+}
+
+/// A presence text field that exceeds Discord's length limit.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TruncatedField {
+    pub field: &'static str,
+    pub full: String,
+    pub truncated: String,
+}
+
+/// Report which presence fields of the current track would be cut off, and how.
+#[tauri::command]
+pub fn check_truncation(state: State<AppState>) -> Vec<TruncatedField> {
+    let Some(track) = state.current_track.lock().unwrap().clone() else {
+        return Vec::new();
+    };
+    let cfg = state.config.lock().unwrap().clone();
+    let now = discord_rpc::now_secs();
+    let opts = crate::build_activity_options(&cfg, now, now);
+    let text = discord_rpc::build_presence_text(&track, &opts, !track.is_playing);
+
+    [
+        ("details", text.details),
+        ("state", text.state),
+        ("largeText", text.large_text),
+    ]
+    .into_iter()
+    .filter(|(_, full)| full.chars().count() > discord_rpc::TEXT_FIELD_LIMIT)
+    .map(|(field, full)| TruncatedField {
+        field,
+        truncated: discord_rpc::truncate_field(
+            &full,
+            discord_rpc::TEXT_FIELD_LIMIT,
+            cfg.truncation_style,
+        ),
+        full,
+    })
+    .collect()
+}
+
+/// Present `track` as if the poll loop had just read it. Polling is paused so the
+/// injected track isn't immediately replaced; resume it from the tray.
+#[cfg(feature = "debug_commands")]
+#[tauri::command]
+pub async fn inject_track(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    track: TrackInfo,
+) -> Result<(), String> {
+    tracing::info!("Injecting track \"{}\"", track.name);
+    let cfg = state.config.lock().unwrap().clone();
+    let artwork_url = if cfg.album_art_enabled() {
+        state
+            .art_resolver
+            .lock()
+            .await
+            .resolve(album_art::query_artist(&track), &track.album, &track.name)
+            .await
+    } else {
+        None
+    };
+
+    set_polling_enabled(state.clone(), false);
+    *state.current_track.lock().unwrap() = Some(track.clone());
+    *state.current_artwork_url.lock().unwrap() = artwork_url.clone();
+    crate::update_now_playing_label(&app, &Some(track.clone()), &cfg);
+
+    // Same presence rules as the poll loop applies to a track it reads
+    if track.is_playing && state.manual_override.swap(false, Ordering::Relaxed) {
+        tracing::info!("Injected track is playing; clearing manual presence override");
+    }
+    if state.manual_override.load(Ordering::Relaxed) {
+        tracing::debug!("Manual presence override active; leaving presence alone");
+    } else if let Some(block) = crate::presence_block(&state, &cfg, Some(&track)) {
+        tracing::debug!("{}", block.reason());
+        if block.clears() {
+            state.discord.clear_presence();
+        }
+    } else if cfg.enable_on_launch {
+        let now = discord_rpc::now_secs();
+        let opts = crate::build_activity_options(&cfg, now, now);
+        if track.is_playing {
+            state.push_presence(|discord| discord.update_track(&track, artwork_url, opts));
+        } else if cfg.idle_behavior == config::IdleBehavior::ShowPaused {
+            state.push_presence(|discord| discord.set_paused(&track, artwork_url, opts));
+        } else {
+            state.discord.clear_presence();
+        }
+    }
+
+    let _ = app.emit("track-changed", Some(&track));
     Ok(())
 }
+
+/// Show `status` in the UI without touching the real connection, for checking how each
+/// variant is displayed.
+#[cfg(feature = "debug_commands")]
+#[tauri::command]
+pub fn debug_set_discord_status(app: AppHandle, state: State<AppState>, status: DiscordStatus) {
+    tracing::info!("Forcing Discord status to {status:?}");
+    state.discord.force_status(status.clone());
+    let _ = app.emit("discord-status-changed", &status);
+}
+
+/// A config field whose value differs from the default.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigChange {
+    pub field: String,
+    pub value: serde_json::Value,
+    pub default: serde_json::Value,
+}
+
+/// List the config fields the user has changed from their defaults.
+#[tauri::command]
+pub fn get_config_diff(state: State<AppState>) -> Result<Vec<ConfigChange>, String> {
+    let cfg = state.config.lock().unwrap().clone();
+    let to_map = |c: &AppConfig| match serde_json::to_value(c) {
+        Ok(serde_json::Value::Object(map)) => Ok(map),
+        Ok(_) => Err("Config did not serialize to an object".to_string()),
+        Err(e) => Err(format!("Failed to serialize config: {e}")),
+    };
+    let current = to_map(&cfg)?;
+    let defaults = to_map(&AppConfig::default())?;
+
+    Ok(current
+        .into_iter()
+        .filter_map(|(field, value)| {
+            let default = defaults.get(&field).cloned().unwrap_or(serde_json::Value::Null);
+            (value != default).then_some(ConfigChange {
+                field,
+                value,
+                default,
+            })
+        })
+        .collect())
+}
+
+/// Forget the cached artwork for the current track, look it up again and re-push the
+/// presence with the result.
+#[tauri::command]
+pub async fn refresh_current_art(state: State<'_, AppState>) -> Result<Option<String>, String> {
+    let track = state
+        .current_track
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| "Nothing is playing".to_string())?;
+    let cfg = state.config.lock().unwrap().clone();
+    if cfg.minimal_mode {
+        return Err("Album art lookups are off in minimal mode".to_string());
+    }
+
+    let artist = album_art::query_artist(&track);
+    let artwork_url = {
+        let mut resolver = state.art_resolver.lock().await;
+        resolver.forget(artist, &track.album, &track.name);
+        resolver.resolve(artist, &track.album, &track.name).await
+    };
+    tracing::info!("Re-resolved artwork for \"{}\": {artwork_url:?}", track.name);
+    *state.current_artwork_url.lock().unwrap() = artwork_url.clone();
+
+    let override_active = state.manual_override.load(Ordering::Relaxed);
+    if let Some(block) = crate::presence_block(&state, &cfg, Some(&track)) {
+        tracing::debug!("Not pushing refreshed art: {}", block.reason());
+    } else if cfg.enable_on_launch && cfg.album_art_enabled() && !override_active {
+        let now = discord_rpc::now_secs();
+        let opts = crate::build_activity_options(&cfg, now, now);
+        if track.is_playing {
+            state.push_presence(|discord| discord.update_track(&track, artwork_url.clone(), opts));
+        } else if cfg.idle_behavior == config::IdleBehavior::ShowPaused {
+            state.push_presence(|discord| discord.set_paused(&track, artwork_url.clone(), opts));
+        }
+    }
+    Ok(artwork_url)
+}
+
+/// Min/avg/max of repeated timings, in milliseconds.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyStats {
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+}
+
+impl LatencyStats {
+    fn from_samples(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let min_ms = samples.iter().copied().fold(f64::INFINITY, f64::min);
+        let max_ms = samples.iter().copied().fold(0.0, f64::max);
+        let avg_ms = samples.iter().sum::<f64>() / samples.len() as f64;
+        Some(Self {
+            min_ms,
+            avg_ms,
+            max_ms,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PollLatency {
+    pub iterations: u32,
+    /// Time to read the current track from Music.app (osascript).
+    pub track_read: Option<LatencyStats>,
+    /// Time for an iTunes artwork lookup for the track read, bypassing the caches.
+    pub art_resolve: Option<LatencyStats>,
+}
+
+/// Time the track read and the artwork resolve separately over a few iterations.
+#[tauri::command]
+pub async fn measure_poll_latency(
+    state: State<'_, AppState>,
+    iterations: Option<u32>,
+) -> Result<PollLatency, String> {
+    let iterations = iterations.unwrap_or(3).clamp(1, 10);
+    let mut track_samples = Vec::new();
+    let mut art_samples = Vec::new();
+
+    for _ in 0..iterations {
+        let started = std::time::Instant::now();
+        let track = tokio::task::spawn_blocking(apple_music::get_current_track)
+            .await
+            .map_err(|e| format!("Track read task failed: {e}"))?;
+        // Failed and skipped (busy) reads return early and would drag the numbers down
+        let Ok(track) = track else {
+            continue;
+        };
+        track_samples.push(started.elapsed().as_secs_f64() * 1000.0);
+
+        // Time the network lookup itself; through the caches every run after the first
+        // would be a hit
+        let lookup = state
+            .art_resolver
+            .lock()
+            .await
+            .time_uncached_lookup(album_art::query_artist(&track), &track.album, &track.name)
+            .await;
+        if let Some(elapsed) = lookup {
+            art_samples.push(elapsed.as_secs_f64() * 1000.0);
+        }
+    }
+
+    let latency = PollLatency {
+        iterations,
+        track_read: LatencyStats::from_samples(&track_samples),
+        art_resolve: LatencyStats::from_samples(&art_samples),
+    };
+    tracing::info!("Poll latency: {latency:?}");
+    Ok(latency)
+}
@@ -1,12 +1,18 @@
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+use crate::album_art::{
+    DEFAULT_ITUNES_RESULT_LIMIT, DEFAULT_MAX_DISK_ENTRIES, DEFAULT_MAX_MEMORY_ENTRIES,
+};
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum DisplayFormat {
     #[default]
     SongArtist,
     ArtistSong,
+    /// Details and state rendered from `details_template` / `state_template`.
+    Custom,
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
@@ -17,6 +23,115 @@ pub enum IdleBehavior {
     ShowPaused,
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MusicQuitBehavior {
+    #[default]
+    Clear,
+    ShowLastPaused,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SmallImageMode {
+    /// No small image or hover text; the activity's assets carry only the large image.
+    None,
+    #[default]
+    SourceLogo,
+    PlayState,
+}
+
+/// How the tray's progress item shows the playback position.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TrayProgressStyle {
+    /// "2:14"
+    Elapsed,
+    /// "-2:16"
+    Remaining,
+    /// "2:14 / 4:30"
+    #[default]
+    ElapsedTotal,
+}
+
+/// What the presence timer counts from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TimestampAnchor {
+    /// The current track's progress.
+    #[default]
+    Track,
+    /// Elapsed time since the listening session started; see `session_gap_secs`.
+    Session,
+}
+
+/// Which rating the star display uses; the other fills in when it's unset.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RatingSource {
+    #[default]
+    Track,
+    Album,
+}
+
+/// Track metadata shown on one presence line.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PresenceField {
+    Track,
+    Artist,
+    Album,
+    /// Leave the line out.
+    None,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NowPlayingClickAction {
+    /// Leave the tray's now-playing item disabled.
+    None,
+    #[default]
+    OpenInMusic,
+    CopyNowPlaying,
+    OpenSettings,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ArtPlacement {
+    #[default]
+    LargeArt,
+    /// Cover as the small image, logo (or play-state icon) as the large one.
+    SmallArt,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AirPlayBehavior {
+    #[default]
+    Normal,
+    /// Add "Listening on {device}" to the small image text.
+    Annotate,
+    /// Clear presence while playing to an AirPlay device.
+    Suppress,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TruncationStyle {
+    #[default]
+    End,
+    Middle,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IdleTrayIcon {
+    #[default]
+    Same,
+    Dimmed,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AppConfig {
@@ -27,13 +142,194 @@ pub struct AppConfig {
     #[serde(default = "default_true")]
     pub show_timestamps: bool,
     #[serde(default)]
+    pub timestamp_anchor: TimestampAnchor,
+    /// A stop longer than this ends the listening session for `TimestampAnchor::Session`.
+    #[serde(default = "default_session_gap")]
+    pub session_gap_secs: u64,
+    #[serde(default)]
     pub display_format: DisplayFormat,
+    /// Per-line overrides of `display_format`; `None` keeps the preset's line.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details_field: Option<PresenceField>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_field: Option<PresenceField>,
     #[serde(default)]
     pub idle_behavior: IdleBehavior,
     #[serde(default = "default_poll_interval")]
     pub poll_interval_secs: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll_interval_playing_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll_interval_paused_secs: Option<u64>,
     #[serde(default)]
     pub launch_at_login: bool,
+    #[serde(default)]
+    pub reject_small_art: bool,
+    #[serde(default = "default_min_art_bytes")]
+    pub min_art_bytes: u64,
+    #[serde(default = "default_true")]
+    pub auto_update_check: bool,
+    #[serde(default = "default_update_check_delay")]
+    pub update_check_delay_secs: u64,
+    /// Re-check for updates this often after the startup check. 0 checks once at launch.
+    #[serde(default)]
+    pub update_check_interval_secs: u64,
+    /// Extra attempts, with backoff, when an automatic update check fails.
+    #[serde(default = "default_update_check_retries")]
+    pub update_check_retries: u32,
+    /// After a check fails all its attempts, try again this much later. 0 waits for the
+    /// next regular check.
+    #[serde(default = "default_update_failure_recheck")]
+    pub update_failure_recheck_secs: u64,
+    /// Delay before the updated app is reopened, giving this process time to exit.
+    #[serde(default = "default_relaunch_delay")]
+    pub relaunch_delay_secs: u64,
+    #[serde(default = "default_max_memory_art_entries")]
+    pub max_memory_art_entries: usize,
+    #[serde(default = "default_max_disk_art_entries")]
+    pub max_disk_art_entries: usize,
+    #[serde(default)]
+    pub fallback_elapsed_timestamp: bool,
+    /// How long a track change waits for album art before presenting with the logo.
+    /// 0 waits for the lookup to finish.
+    #[serde(default)]
+    pub art_wait_timeout_ms: u64,
+    #[serde(default)]
+    pub truncate_tray_at_word_boundary: bool,
+    /// Show the now-playing text next to the tray icon, not only in the menu.
+    #[serde(default)]
+    pub show_track_in_menubar: bool,
+    #[serde(default)]
+    pub tray_progress_style: TrayProgressStyle,
+    #[serde(default)]
+    pub show_queue_as_party: bool,
+    #[serde(default)]
+    pub min_track_duration_secs: u64,
+    #[serde(default)]
+    pub small_image_mode: SmallImageMode,
+    #[serde(default)]
+    pub music_quit_behavior: MusicQuitBehavior,
+    #[serde(default = "default_music_quit_grace")]
+    pub music_quit_grace_secs: u64,
+    #[serde(default)]
+    pub idle_tray_icon: IdleTrayIcon,
+    #[serde(default)]
+    pub show_quality_badge: bool,
+    /// Add the rating as stars to the small image text.
+    #[serde(default)]
+    pub show_rating_stars: bool,
+    #[serde(default)]
+    pub rating_source: RatingSource,
+    #[serde(default = "default_wake_detection_slack")]
+    pub wake_detection_slack_secs: u64,
+    /// `None` uses `DEFAULT_DETAILS_TEMPLATE`; see `AppConfig::details_template`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details_template: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_template: Option<String>,
+    #[serde(default)]
+    pub hide_explicit_art: bool,
+    /// Image URL or Discord asset key always used as the large image.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixed_large_image_url: Option<String>,
+    #[serde(default)]
+    pub verify_art_reachable: bool,
+    #[serde(default)]
+    pub refresh_expiring_art: bool,
+    /// While offline, keep showing expired cached covers instead of the logo.
+    #[serde(default = "default_true")]
+    pub offline_stale_art: bool,
+    /// On battery power, use only cached art (or the logo) instead of looking it up.
+    #[serde(default)]
+    pub pause_art_on_battery: bool,
+    #[serde(default)]
+    pub truncation_style: TruncationStyle,
+    #[serde(default)]
+    pub paused_hide_art: bool,
+    #[serde(default)]
+    pub show_version_in_presence: bool,
+    #[serde(default = "default_art_concurrency")]
+    pub art_concurrency: u32,
+    /// iTunes results fetched per artwork lookup and ranked by how well they match.
+    #[serde(default = "default_itunes_result_limit")]
+    pub itunes_result_limit: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_stop_after_hours: Option<u64>,
+    #[serde(default)]
+    pub airplay_behavior: AirPlayBehavior,
+    #[serde(default)]
+    pub art_placement: ArtPlacement,
+    #[serde(default)]
+    pub log_discord_payloads: bool,
+    /// Stop reconnecting to Discord after this many failed attempts in a row, until
+    /// `reconnect_discord` is called. `None` retries forever.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_reconnect_attempts: Option<u32>,
+    /// Re-send the current presence this often while nothing changes, so long-static
+    /// activities don't expire. `None` (the default) sends no heartbeat.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_heartbeat_secs: Option<u64>,
+    /// Battery saver: no art, no timestamps, paused poll interval. Overrides the stored
+    /// settings without changing them.
+    #[serde(default)]
+    pub minimal_mode: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_badge_days: Option<u32>,
+    #[serde(default)]
+    pub now_playing_click_action: NowPlayingClickAction,
+    /// Clear presence while a local (non-catalog) file is playing.
+    #[serde(default)]
+    pub hide_local_files: bool,
+    /// When non-empty, presence is only shown while playing from one of these playlists
+    /// (matched case-insensitively) and is cleared otherwise.
+    #[serde(default)]
+    pub broadcast_only_playlists: Vec<String>,
+    /// Debounce the `track-changed` event and track-change script: only the last change in
+    /// a burst goes out, once this long passes without another. 0 sends every change.
+    #[serde(default)]
+    pub track_event_min_interval_secs: u64,
+    /// Scripts run when a new track starts playing and when playback pauses. See
+    /// `hooks::run_hook` for how they're invoked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_track_change_script: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_pause_script: Option<PathBuf>,
+    /// Shown as a static presence while nothing is playing. `None` clears presence instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_presence: Option<String>,
+}
+
+pub const DEFAULT_DETAILS_TEMPLATE: &str = "{name}";
+pub const DEFAULT_STATE_TEMPLATE: &str = "by {artist}";
+
+impl AppConfig {
+    /// `details_template`, or [`DEFAULT_DETAILS_TEMPLATE`] while it is unset.
+    pub fn details_template(&self) -> &str {
+        self.details_template
+            .as_deref()
+            .unwrap_or(DEFAULT_DETAILS_TEMPLATE)
+    }
+
+    /// `state_template`, or [`DEFAULT_STATE_TEMPLATE`] while it is unset.
+    pub fn state_template(&self) -> &str {
+        self.state_template.as_deref().unwrap_or(DEFAULT_STATE_TEMPLATE)
+    }
+
+    /// Whether either template has been set, which is when `DisplayFormat::Custom` is
+    /// offered in the tray.
+    pub fn has_custom_templates(&self) -> bool {
+        self.details_template.is_some() || self.state_template.is_some()
+    }
+
+    /// `show_album_art`, unless minimal mode turns it off.
+    pub fn album_art_enabled(&self) -> bool {
+        self.show_album_art && !self.minimal_mode
+    }
+
+    /// `show_timestamps`, unless minimal mode turns it off.
+    pub fn timestamps_enabled(&self) -> bool {
+        self.show_timestamps && !self.minimal_mode
+    }
 }
 
 fn default_true() -> bool {
@@ -44,16 +340,123 @@ fn default_poll_interval() -> u64 {
     5
 }
 
+fn default_min_art_bytes() -> u64 {
+    8 * 1024
+}
+
+fn default_update_check_delay() -> u64 {
+    10
+}
+
+fn default_update_check_retries() -> u32 {
+    2
+}
+
+fn default_update_failure_recheck() -> u64 {
+    3600
+}
+
+fn default_relaunch_delay() -> u64 {
+    1
+}
+
+fn default_music_quit_grace() -> u64 {
+    60
+}
+
+fn default_wake_detection_slack() -> u64 {
+    10
+}
+
+fn default_session_gap() -> u64 {
+    600
+}
+
+fn default_art_concurrency() -> u32 {
+    1
+}
+
+fn default_itunes_result_limit() -> u32 {
+    DEFAULT_ITUNES_RESULT_LIMIT
+}
+
+fn default_max_memory_art_entries() -> usize {
+    DEFAULT_MAX_MEMORY_ENTRIES
+}
+
+fn default_max_disk_art_entries() -> usize {
+    DEFAULT_MAX_DISK_ENTRIES
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
             enable_on_launch: true,
             show_album_art: true,
             show_timestamps: true,
+            timestamp_anchor: TimestampAnchor::default(),
+            session_gap_secs: default_session_gap(),
             display_format: DisplayFormat::default(),
+            details_field: None,
+            state_field: None,
             idle_behavior: IdleBehavior::default(),
             poll_interval_secs: 5,
+            poll_interval_playing_secs: None,
+            poll_interval_paused_secs: None,
             launch_at_login: false,
+            reject_small_art: false,
+            min_art_bytes: default_min_art_bytes(),
+            auto_update_check: true,
+            update_check_delay_secs: default_update_check_delay(),
+            update_check_interval_secs: 0,
+            update_check_retries: default_update_check_retries(),
+            update_failure_recheck_secs: default_update_failure_recheck(),
+            relaunch_delay_secs: default_relaunch_delay(),
+            max_memory_art_entries: DEFAULT_MAX_MEMORY_ENTRIES,
+            max_disk_art_entries: DEFAULT_MAX_DISK_ENTRIES,
+            fallback_elapsed_timestamp: false,
+            art_wait_timeout_ms: 0,
+            truncate_tray_at_word_boundary: false,
+            show_track_in_menubar: false,
+            tray_progress_style: TrayProgressStyle::default(),
+            show_queue_as_party: false,
+            min_track_duration_secs: 0,
+            small_image_mode: SmallImageMode::default(),
+            music_quit_behavior: MusicQuitBehavior::default(),
+            music_quit_grace_secs: default_music_quit_grace(),
+            idle_tray_icon: IdleTrayIcon::default(),
+            show_quality_badge: false,
+            show_rating_stars: false,
+            rating_source: RatingSource::default(),
+            wake_detection_slack_secs: default_wake_detection_slack(),
+            details_template: None,
+            state_template: None,
+            hide_explicit_art: false,
+            fixed_large_image_url: None,
+            verify_art_reachable: false,
+            refresh_expiring_art: false,
+            offline_stale_art: true,
+            pause_art_on_battery: false,
+            truncation_style: TruncationStyle::default(),
+            paused_hide_art: false,
+            show_version_in_presence: false,
+            art_concurrency: default_art_concurrency(),
+            itunes_result_limit: default_itunes_result_limit(),
+            auto_stop_after_hours: None,
+            airplay_behavior: AirPlayBehavior::default(),
+            art_placement: ArtPlacement::default(),
+            log_discord_payloads: false,
+            max_reconnect_attempts: None,
+            presence_heartbeat_secs: None,
+            minimal_mode: false,
+            new_badge_days: None,
+            now_playing_click_action: NowPlayingClickAction::default(),
+            hide_local_files: false,
+            broadcast_only_playlists: Vec::new(),
+            track_event_min_interval_secs: 0,
+            on_track_change_script: None,
+            on_pause_script: None,
+            idle_presence: None,
         }
     }
 }
@@ -84,3 +487,27 @@ pub fn save_config(config: &AppConfig) -> Result<(), String> {
     std::fs::write(&path, json).map_err(|e| format!("Failed to write config: {e}"))?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn itunes_result_limit_defaults_when_missing() {
+        let cfg: AppConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(cfg.itunes_result_limit, DEFAULT_ITUNES_RESULT_LIMIT);
+        assert_eq!(
+            AppConfig::default().itunes_result_limit,
+            DEFAULT_ITUNES_RESULT_LIMIT
+        );
+    }
+
+    #[test]
+    fn itunes_result_limit_round_trips() {
+        let cfg: AppConfig = serde_json::from_str(r#"{"itunesResultLimit": 12}"#).unwrap();
+        assert_eq!(cfg.itunes_result_limit, 12);
+        let saved = serde_json::to_string(&cfg).unwrap();
+        let reloaded: AppConfig = serde_json::from_str(&saved).unwrap();
+        assert_eq!(reloaded.itunes_result_limit, 12);
+    }
+}
@@ -1,33 +1,127 @@
+use std::collections::VecDeque;
 use std::sync::mpsc::{self, Sender};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use discord_rich_presence::activity::{Activity, ActivityType, Assets, Timestamps};
+use discord_rich_presence::activity::{Activity, ActivityType, Assets, Party, Timestamps};
 use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::apple_music::TrackInfo;
-use crate::config::DisplayFormat;
+use crate::config::{
+    ArtPlacement, DisplayFormat, PresenceField, RatingSource, SmallImageMode, TruncationStyle,
+};
+use crate::template;
 
 /// Replace with your Discord Application ID.
 /// Create one at https://discord.com/developers/applications
 const DISCORD_APP_ID: &str = "1470809241907363921";
 
+/// Asset keys the activity builders will refer to with `small_image_mode`; each must be
+/// uploaded to the Discord app. The logo is always the large image fallback.
+pub fn expected_asset_keys(small_image_mode: SmallImageMode) -> Vec<&'static str> {
+    match small_image_mode {
+        SmallImageMode::PlayState => vec!["apple_music_logo", "playing", "paused"],
+        SmallImageMode::None | SmallImageMode::SourceLogo => vec!["apple_music_logo"],
+    }
+}
+
+/// Which `expected_asset_keys` the Discord app has uploaded.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetCheck {
+    pub app_id: &'static str,
+    pub present: Vec<String>,
+    pub missing: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ApplicationAsset {
+    name: String,
+}
+
+/// Look up the app's uploaded assets on Discord's public endpoint and compare them
+/// against the keys `small_image_mode` needs.
+pub async fn verify_assets(small_image_mode: SmallImageMode) -> Result<AssetCheck, String> {
+    let url = format!("https://discord.com/api/v9/oauth2/applications/{DISCORD_APP_ID}/assets");
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach Discord: {e}"))?;
+    if !response.status().is_success() {
+        return Err(format!("Discord returned {} for the asset list", response.status()));
+    }
+    let assets: Vec<ApplicationAsset> = response
+        .json()
+        .await
+        .map_err(|e| format!("Unexpected asset list from Discord: {e}"))?;
+
+    let (present, missing) = expected_asset_keys(small_image_mode)
+        .into_iter()
+        .map(|key| key.to_string())
+        .partition(|key| assets.iter().any(|asset| asset.name == *key));
+    Ok(AssetCheck {
+        app_id: DISCORD_APP_ID,
+        present,
+        missing,
+    })
+}
+
+/// Consecutive activity update failures before the status suggests checking Discord's
+/// Activity Privacy settings.
+const ACTIVITY_FAILURE_HINT_THRESHOLD: u32 = 3;
+
+#[derive(Clone)]
 pub struct ActivityOptions {
     pub show_timestamps: bool,
+    /// Count up from this unix time (the listening session start) instead of showing the
+    /// track's progress.
+    pub session_started_at: Option<i64>,
     pub show_album_art: bool,
     pub display_format: DisplayFormat,
+    /// When the track has no usable duration, show an elapsed timer from this unix time.
+    pub fallback_started_at: Option<i64>,
+    pub show_queue_as_party: bool,
+    pub small_image_mode: SmallImageMode,
+    pub show_quality_badge: bool,
+    /// Show the rating from this source as stars in the small image text.
+    pub rating_stars: Option<RatingSource>,
+    pub details_template: String,
+    pub state_template: String,
+    pub fixed_large_image: Option<String>,
+    pub truncation_style: TruncationStyle,
+    pub paused_hide_art: bool,
+    pub show_version_in_presence: bool,
+    pub annotate_airplay: bool,
+    pub art_placement: ArtPlacement,
+    /// Log each activity payload at debug level before it's sent.
+    pub log_payloads: bool,
+    /// Add a "New!" badge to tracks added to the library within this many days.
+    pub new_badge_days: Option<u32>,
+    /// Overrides for the details and state lines chosen by `display_format`.
+    pub details_field: Option<PresenceField>,
+    pub state_field: Option<PresenceField>,
 }
 
 #[allow(dead_code)]
 pub enum DiscordCommand {
     UpdateTrack(TrackInfo, Option<String>, ActivityOptions),
     SetPaused(TrackInfo, Option<String>, ActivityOptions),
+    SetIdle(String, ActivityOptions),
     ClearPresence,
-    Shutdown,
+    /// Start reconnecting again after `max_reconnect_attempts` ran out.
+    Reconnect,
+    /// Clear presence and close the socket, then signal the sender if one is given.
+    Shutdown(Option<Sender<()>>),
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum DiscordStatus {
     Disconnected,
@@ -36,67 +130,273 @@ pub enum DiscordStatus {
     Error(String),
 }
 
+/// Number of status transitions kept for diagnostics.
+const STATUS_HISTORY_LIMIT: usize = 20;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusChange {
+    pub at: i64,
+    pub status: DiscordStatus,
+}
+
+/// Current status plus the most recent transitions, oldest first.
+pub struct StatusState {
+    current: DiscordStatus,
+    history: VecDeque<StatusChange>,
+}
+
+impl StatusState {
+    fn new() -> Self {
+        Self {
+            current: DiscordStatus::Disconnected,
+            history: VecDeque::new(),
+        }
+    }
+}
+
+type SharedStatus = Arc<Mutex<StatusState>>;
+
+/// The last presence sent: a track (with artwork URL, paused flag and options) or an
+/// idle message.
+#[derive(Clone)]
+enum PendingPresence {
+    Track(TrackInfo, Option<String>, bool, ActivityOptions),
+    Idle(String, ActivityOptions),
+}
+
+/// Pause between clearing presence and closing the socket on shutdown.
+const SHUTDOWN_GRACE: Duration = Duration::from_millis(250);
+/// Longest `DiscordManager::shutdown` waits for the Discord thread.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Delay before restarting the Discord thread after a panic.
+const THREAD_RESTART_DELAY: Duration = Duration::from_secs(5);
+
+/// Discord allows 5 activity updates per 20 seconds.
+const MIN_HEARTBEAT_SECS: u64 = 20;
+
+/// How often a connected socket is pinged to catch Discord dropping it without closing
+/// (e.g. after a crash), which otherwise only shows up when a presence write fails.
+const LIVENESS_CHECK_INTERVAL: Duration = Duration::from_secs(MIN_HEARTBEAT_SECS);
+
+/// Discord IPC opcode for a ping frame.
+const IPC_OPCODE_PING: u8 = 3;
+
 pub struct DiscordManager {
     tx: Sender<DiscordCommand>,
-    pub status: Arc<Mutex<DiscordStatus>>,
+    pub status: SharedStatus,
+    /// Last presence requested, replayed if the Discord thread has to be restarted.
+    last_track: Arc<Mutex<Option<PendingPresence>>>,
+    settings: Arc<ThreadSettings>,
+}
+
+/// Config the Discord thread reads on every iteration.
+struct ThreadSettings {
+    /// Heartbeat interval; 0 sends no heartbeats.
+    heartbeat_secs: AtomicU64,
+    /// Failed reconnects before giving up; 0 retries forever.
+    max_reconnect_attempts: AtomicU32,
+}
+
+impl ThreadSettings {
+    fn new() -> Self {
+        Self {
+            heartbeat_secs: AtomicU64::new(0),
+            max_reconnect_attempts: AtomicU32::new(0),
+        }
+    }
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> &str {
+    if let Some(s) = panic.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = panic.downcast_ref::<String>() {
+        s
+    } else {
+        "unknown panic"
+    }
 }
 
 impl DiscordManager {
     pub fn start() -> Self {
         let (tx, rx) = mpsc::channel();
-        let status = Arc::new(Mutex::new(DiscordStatus::Disconnected));
+        let status = Arc::new(Mutex::new(StatusState::new()));
+        let last_track = Arc::new(Mutex::new(None));
         let thread_status = Arc::clone(&status);
+        let thread_last_track = Arc::clone(&last_track);
+        let settings = Arc::new(ThreadSettings::new());
+        let thread_settings = Arc::clone(&settings);
 
-        std::thread::spawn(move || {
-            discord_thread_main(rx, thread_status);
+        // Supervisor: a panic in the Discord thread restarts it and replays the last track
+        std::thread::spawn(move || loop {
+            let replay = thread_last_track.lock().unwrap().clone();
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                discord_thread_main(
+                    &rx,
+                    Arc::clone(&thread_status),
+                    replay,
+                    &thread_settings,
+                )
+            }));
+            let Err(panic) = result else {
+                break;
+            };
+            tracing::error!(
+                "Discord thread panicked: {}; restarting in {}s",
+                panic_message(panic.as_ref()),
+                THREAD_RESTART_DELAY.as_secs()
+            );
+            thread_status.clear_poison();
+            set_status(&thread_status, DiscordStatus::Disconnected);
+            std::thread::sleep(THREAD_RESTART_DELAY);
         });
 
-        Self { tx, status }
+        Self {
+            tx,
+            status,
+            last_track,
+            settings,
+        }
+    }
+
+    /// A manager with no Discord thread behind it; every command is silently dropped.
+    pub fn disabled() -> Self {
+        let (tx, _rx) = mpsc::channel();
+        let status = Arc::new(Mutex::new(StatusState::new()));
+        Self {
+            tx,
+            status,
+            last_track: Arc::new(Mutex::new(None)),
+            settings: Arc::new(ThreadSettings::new()),
+        }
+    }
+
+    /// How often the current presence is re-sent while nothing changes; `None` turns the
+    /// heartbeat off. This keeps long-static (e.g. paused) activities from expiring; the
+    /// socket's liveness is checked separately (see `LIVENESS_CHECK_INTERVAL`). Floored at
+    /// `MIN_HEARTBEAT_SECS` so a hand-edited config can't hit Discord's rate limit.
+    pub fn set_heartbeat_interval(&self, secs: Option<u64>) {
+        let secs = secs.map_or(0, |secs| secs.max(MIN_HEARTBEAT_SECS));
+        self.settings.heartbeat_secs.store(secs, Ordering::Relaxed);
+    }
+
+    /// Stop reconnecting after this many failed attempts in a row; `None` retries forever.
+    pub fn set_max_reconnect_attempts(&self, attempts: Option<u32>) {
+        self.settings
+            .max_reconnect_attempts
+            .store(attempts.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    /// Reconnect after giving up; a no-op while connected or still retrying.
+    pub fn reconnect(&self) {
+        let _ = self.tx.send(DiscordCommand::Reconnect);
     }
 
     pub fn update_track(&self, track: &TrackInfo, artwork_url: Option<String>, opts: ActivityOptions) {
+        *self.last_track.lock().unwrap() = Some(PendingPresence::Track(
+            track.clone(),
+            artwork_url.clone(),
+            false,
+            opts.clone(),
+        ));
         let _ = self
             .tx
             .send(DiscordCommand::UpdateTrack(track.clone(), artwork_url, opts));
     }
 
     pub fn set_paused(&self, track: &TrackInfo, artwork_url: Option<String>, opts: ActivityOptions) {
+        *self.last_track.lock().unwrap() = Some(PendingPresence::Track(
+            track.clone(),
+            artwork_url.clone(),
+            true,
+            opts.clone(),
+        ));
         let _ = self
             .tx
             .send(DiscordCommand::SetPaused(track.clone(), artwork_url, opts));
     }
 
+    /// Show a static idle message instead of a track.
+    pub fn set_idle(&self, text: &str, opts: ActivityOptions) {
+        *self.last_track.lock().unwrap() =
+            Some(PendingPresence::Idle(text.to_string(), opts.clone()));
+        let _ = self.tx.send(DiscordCommand::SetIdle(text.to_string(), opts));
+    }
+
     pub fn clear_presence(&self) {
+        *self.last_track.lock().unwrap() = None;
         let _ = self.tx.send(DiscordCommand::ClearPresence);
     }
 
-    #[allow(dead_code)]
+    /// Clear presence and disconnect, blocking until the Discord thread is done (at most
+    /// `SHUTDOWN_TIMEOUT`), so the clear isn't lost when the process exits right after.
     pub fn shutdown(&self) {
-        let _ = self.tx.send(DiscordCommand::Shutdown);
+        let (ack_tx, ack_rx) = mpsc::channel();
+        if self.tx.send(DiscordCommand::Shutdown(Some(ack_tx))).is_ok() {
+            let _ = ack_rx.recv_timeout(SHUTDOWN_TIMEOUT);
+        }
     }
 
     pub fn get_status(&self) -> DiscordStatus {
-        self.status.lock().unwrap().clone()
+        self.status.lock().unwrap().current.clone()
+    }
+
+    pub fn get_status_history(&self) -> Vec<StatusChange> {
+        self.status.lock().unwrap().history.iter().cloned().collect()
+    }
+
+    /// Report `status` without touching the connection; the Discord thread overwrites it
+    /// on its next real transition.
+    #[cfg(feature = "debug_commands")]
+    pub fn force_status(&self, status: DiscordStatus) {
+        set_status(&self.status, status);
     }
 }
 
-fn set_status(status: &Arc<Mutex<DiscordStatus>>, new_status: DiscordStatus) {
-    *status.lock().unwrap() = new_status;
+fn set_status(status: &SharedStatus, new_status: DiscordStatus) {
+    let mut state = status.lock().unwrap();
+    if state.current != new_status {
+        state.history.push_back(StatusChange {
+            at: now_secs(),
+            status: new_status.clone(),
+        });
+        while state.history.len() > STATUS_HISTORY_LIMIT {
+            state.history.pop_front();
+        }
+    }
+    state.current = new_status;
+}
+
+/// Build the error status for a failed activity update. A single failure is usually a
+/// transient socket problem; repeated failures point at Discord refusing activities.
+fn activity_error_status(failures: u32, e: &dyn std::fmt::Display) -> DiscordStatus {
+    if failures >= ACTIVITY_FAILURE_HINT_THRESHOLD {
+        DiscordStatus::Error(format!(
+            "Discord keeps rejecting activity updates ({e}). Check that \"Share your detected \
+             activities with others\" is enabled in Discord under Settings \u{2192} Activity Privacy."
+        ))
+    } else {
+        DiscordStatus::Error(format!("Activity update failed: {e}"))
+    }
 }
 
 fn try_connect(client: &mut DiscordIpcClient) -> bool {
     client.connect().is_ok()
 }
 
-fn now_secs() -> i64 {
+pub fn now_secs() -> i64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap_or_default()
         .as_secs() as i64
 }
 
+/// Discord's maximum length for activity text fields.
+pub const TEXT_FIELD_LIMIT: usize = 128;
+
 /// Truncate a string to at most `max_len` characters (UTF-8 safe).
-fn truncate(s: &str, max_len: usize) -> &str {
+pub fn truncate(s: &str, max_len: usize) -> &str {
     if s.len() <= max_len {
         return s;
     }
@@ -106,44 +406,260 @@ fn truncate(s: &str, max_len: usize) -> &str {
     }
 }
 
+/// Shorten `s` to at most `max_len` characters by replacing its middle with an
+/// ellipsis, so both the start and the end stay visible (UTF-8 safe).
+pub fn truncate_middle(s: &str, max_len: usize) -> String {
+    let len = s.chars().count();
+    if len <= max_len {
+        return s.to_string();
+    }
+    let keep = max_len.saturating_sub(1);
+    let head = keep - keep / 2;
+    let tail = keep / 2;
+    let start: String = s.chars().take(head).collect();
+    let end: String = s.chars().skip(len - tail).collect();
+    format!("{}\u{2026}{}", start.trim_end(), end.trim_start())
+}
+
+/// Fit a text field into `max_len` characters using the configured style.
+pub fn truncate_field(s: &str, max_len: usize, style: TruncationStyle) -> String {
+    match style {
+        TruncationStyle::End => truncate(s, max_len).to_string(),
+        TruncationStyle::Middle => truncate_middle(s, max_len),
+    }
+}
+
+/// Sample rate above which a track counts as hi-res (anything past 48 kHz).
+const HI_RES_MIN_SAMPLE_RATE: u32 = 48_001;
+/// CD-quality sample rate; lossless files at this rate are labelled "Lossless".
+const LOSSLESS_MIN_SAMPLE_RATE: u32 = 44_100;
+/// Bit rate (kbps) above the highest lossy AAC/MP3 encodings.
+const LOSSLESS_MIN_BIT_RATE: u32 = 400;
+
+/// Quality label for the track, or `None` when it's lossy or the data is missing
+/// (streams usually report neither value).
+fn quality_badge(track: &TrackInfo) -> Option<&'static str> {
+    let sample_rate = track.sample_rate?;
+    if sample_rate >= HI_RES_MIN_SAMPLE_RATE {
+        Some("Hi-Res")
+    } else if sample_rate >= LOSSLESS_MIN_SAMPLE_RATE
+        && track.bit_rate? >= LOSSLESS_MIN_BIT_RATE
+    {
+        Some("Lossless")
+    } else {
+        None
+    }
+}
+
+/// The rating from `source` (falling back to the other one) as five filled/empty stars,
+/// rounded to the nearest whole star.
+fn rating_stars(track: &TrackInfo, source: RatingSource) -> Option<String> {
+    let rating = match source {
+        RatingSource::Track => track.rating.or(track.album_rating),
+        RatingSource::Album => track.album_rating.or(track.rating),
+    }?;
+    let filled = ((rating as usize + 10) / 20).min(5);
+    Some(format!("{}{}", "\u{2605}".repeat(filled), "\u{2606}".repeat(5 - filled)))
+}
+
+/// Render a display template; a template that fails to parse is shown as written.
+fn render_template(template: &str, track: &TrackInfo) -> String {
+    template::render(template, track)
+        .map(|r| r.text)
+        .unwrap_or_else(|_| template.to_string())
+}
+
+/// Large image: the fixed override if configured, else the artwork or the logo.
+fn large_image<'a>(artwork_url: Option<&'a str>, opts: &'a ActivityOptions) -> &'a str {
+    if let Some(fixed) = &opts.fixed_large_image {
+        return fixed;
+    }
+    if opts.show_album_art {
+        artwork_url.unwrap_or("apple_music_logo")
+    } else {
+        "apple_music_logo"
+    }
+}
+
+/// Small image asset key and hover text for the configured mode, or `None` to omit it.
+fn small_image(
+    track: &TrackInfo,
+    opts: &ActivityOptions,
+    paused: bool,
+) -> Option<(&'static str, String)> {
+    let (key, text) = match opts.small_image_mode {
+        SmallImageMode::None => return None,
+        SmallImageMode::SourceLogo => ("apple_music_logo", "Apple Music"),
+        SmallImageMode::PlayState if paused => ("paused", "Paused"),
+        SmallImageMode::PlayState => ("playing", "Playing"),
+    };
+    let mut text = text.to_string();
+    if let Some(badge) = quality_badge(track).filter(|_| opts.show_quality_badge) {
+        text.push_str(&format!(" \u{00B7} {badge}"));
+    }
+    if let Some(stars) = opts.rating_stars.and_then(|source| rating_stars(track, source)) {
+        text.push_str(&format!(" \u{00B7} {stars}"));
+    }
+    let recently_added = opts
+        .new_badge_days
+        .zip(track.added_days_ago)
+        .is_some_and(|(limit, days)| days <= limit);
+    if recently_added {
+        text.push_str(" \u{00B7} New!");
+    }
+    if let Some(device) = track.airplay_device.as_deref().filter(|_| opts.annotate_airplay) {
+        text.push_str(&format!(" \u{00B7} Listening on {device}"));
+    }
+    if opts.show_version_in_presence {
+        text.push_str(concat!(" \u{00B7} AMDP ", env!("CARGO_PKG_VERSION")));
+    }
+    Some((key, truncate(&text, TEXT_FIELD_LIMIT).to_string()))
+}
+
+/// Discord's party slot ("3 of 12") for the track's playlist position, if enabled and known.
+fn queue_party(track: &TrackInfo, opts: &ActivityOptions) -> Option<Party<'static>> {
+    if !opts.show_queue_as_party {
+        return None;
+    }
+    let (index, total) = (track.queue_index?, track.queue_total?);
+    if index > total {
+        return None;
+    }
+    Some(Party::new().id("amdp-queue").size([index as i32, total as i32]))
+}
+
+/// Activity text lines before truncation to `TEXT_FIELD_LIMIT`.
+pub struct PresenceText {
+    pub details: String,
+    pub state: String,
+    pub large_text: String,
+}
+
+fn presence_field(track: &TrackInfo, field: PresenceField) -> String {
+    match field {
+        PresenceField::Track => track.name.clone(),
+        PresenceField::Artist => track.artist.clone(),
+        PresenceField::Album => track.album.clone(),
+        PresenceField::None => String::new(),
+    }
+}
+
+/// Build the activity's text lines for `track`; paused activities replace the state line.
+/// An empty line is left out of the activity.
+pub fn build_presence_text(track: &TrackInfo, opts: &ActivityOptions, paused: bool) -> PresenceText {
+    let (details, state) = match opts.display_format {
+        DisplayFormat::SongArtist => (track.name.clone(), format!("by {}", track.artist)),
+        DisplayFormat::ArtistSong => (track.artist.clone(), track.name.clone()),
+        DisplayFormat::Custom => (
+            render_template(&opts.details_template, track),
+            render_template(&opts.state_template, track),
+        ),
+    };
+    let details = opts
+        .details_field
+        .map_or(details, |field| presence_field(track, field));
+    let state = opts
+        .state_field
+        .map_or(state, |field| presence_field(track, field));
+    PresenceText {
+        details,
+        state: if paused { "Paused".to_string() } else { state },
+        large_text: track.album.clone(),
+    }
+}
+
+/// Truncate every field of `text` to Discord's limit.
+fn fit_presence_text(text: PresenceText, style: TruncationStyle) -> PresenceText {
+    PresenceText {
+        details: truncate_field(&text.details, TEXT_FIELD_LIMIT, style),
+        state: truncate_field(&text.state, TEXT_FIELD_LIMIT, style),
+        large_text: truncate_field(&text.large_text, TEXT_FIELD_LIMIT, style),
+    }
+}
+
+/// Image slots for the activity. `SmallArt` moves the cover to the small slot (its
+/// hover showing the album) and the small image's asset and text to the large slot.
+fn build_assets<'a>(
+    large_image: &'a str,
+    large_text: &'a str,
+    small: &'a Option<(&'static str, String)>,
+    opts: &ActivityOptions,
+) -> Assets<'a> {
+    let is_cover = large_image != "apple_music_logo";
+    if opts.art_placement == ArtPlacement::SmallArt && is_cover {
+        let (logo, logo_text) = match small {
+            Some((key, text)) => (*key, text.as_str()),
+            None => ("apple_music_logo", "Apple Music"),
+        };
+        return Assets::new()
+            .large_image(logo)
+            .large_text(logo_text)
+            .small_image(large_image)
+            .small_text(large_text);
+    }
+
+    let mut assets = Assets::new().large_image(large_image).large_text(large_text);
+    if let Some((key, text)) = small {
+        assets = assets.small_image(key).small_text(text);
+    }
+    assets
+}
+
+fn log_payload(activity: &Activity, opts: &ActivityOptions) {
+    if !opts.log_payloads {
+        return;
+    }
+    match serde_json::to_string(activity) {
+        Ok(json) => tracing::debug!("Discord activity payload: {json}"),
+        Err(e) => tracing::debug!("Failed to serialize activity payload: {e}"),
+    }
+}
+
 fn set_activity_from_track(
     client: &mut DiscordIpcClient,
     track: &TrackInfo,
     artwork_url: Option<&str>,
     opts: &ActivityOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let (details_text, state_text) = match opts.display_format {
-        DisplayFormat::SongArtist => (track.name.clone(), format!("by {}", track.artist)),
-        DisplayFormat::ArtistSong => (track.artist.clone(), track.name.clone()),
-    };
+    let text = fit_presence_text(build_presence_text(track, opts, false), opts.truncation_style);
 
-    let large_image = if opts.show_album_art {
-        artwork_url.unwrap_or("apple_music_logo")
-    } else {
-        "apple_music_logo"
-    };
+    let large_image = large_image(artwork_url, opts);
 
-    let assets = Assets::new()
-        .large_image(large_image)
-        .large_text(truncate(&track.album, 128))
-        .small_image("apple_music_logo")
-        .small_text("Apple Music");
+    let small = small_image(track, opts, false);
+    let assets = build_assets(large_image, &text.large_text, &small, opts);
 
     let mut activity = Activity::new()
         .activity_type(ActivityType::Listening)
-        .details(truncate(&details_text, 128))
-        .state(truncate(&state_text, 128))
         .assets(assets);
+    if !text.details.is_empty() {
+        activity = activity.details(&text.details);
+    }
+    if !text.state.is_empty() {
+        activity = activity.state(&text.state);
+    }
 
     if opts.show_timestamps {
         let now = now_secs();
         let position_secs = track.position_secs as i64;
         let duration_secs = track.duration_secs as i64;
-        let start_ts = now - position_secs;
-        let end_ts = start_ts + duration_secs;
-        activity = activity.timestamps(Timestamps::new().start(start_ts).end(end_ts));
+        if let Some(session_started_at) = opts.session_started_at {
+            // One elapsed timer running across the whole session
+            activity = activity.timestamps(Timestamps::new().start(session_started_at));
+        } else if duration_secs > 0 {
+            let start_ts = now - position_secs;
+            let end_ts = start_ts + duration_secs;
+            activity = activity.timestamps(Timestamps::new().start(start_ts).end(end_ts));
+        } else if let Some(started_at) = opts.fallback_started_at {
+            // Streams and zero-length tracks: elapsed-only timer
+            activity = activity.timestamps(Timestamps::new().start(started_at));
+        }
     }
 
+    if let Some(party) = queue_party(track, opts) {
+        activity = activity.party(party);
+    }
+
+    log_payload(&activity, opts);
     client.set_activity(activity)?;
     Ok(())
 }
@@ -154,38 +670,107 @@ fn set_paused_activity(
     artwork_url: Option<&str>,
     opts: &ActivityOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let details_text = match opts.display_format {
-        DisplayFormat::SongArtist => track.name.clone(),
-        DisplayFormat::ArtistSong => track.artist.clone(),
-    };
+    let text = fit_presence_text(build_presence_text(track, opts, true), opts.truncation_style);
 
-    let large_image = if opts.show_album_art {
-        artwork_url.unwrap_or("apple_music_logo")
-    } else {
-        "apple_music_logo"
-    };
+    // With paused_hide_art the cover gives way to the logo; a logo small image would
+    // then just repeat it, so it's dropped (the playState "paused" icon is kept).
+    let hide_art = opts.paused_hide_art && opts.fixed_large_image.is_none();
+    let large_image = large_image(artwork_url.filter(|_| !hide_art), opts);
 
-    let assets = Assets::new()
-        .large_image(large_image)
-        .large_text(truncate(&track.album, 128))
-        .small_image("apple_music_logo")
-        .small_text("Apple Music");
+    let small = small_image(track, opts, true)
+        .filter(|(key, _)| !(hide_art && *key == "apple_music_logo"));
+    let assets = build_assets(large_image, &text.large_text, &small, opts);
 
-    let activity = Activity::new()
+    let mut activity = Activity::new()
         .activity_type(ActivityType::Listening)
-        .details(truncate(&details_text, 128))
-        .state("Paused")
         .assets(assets);
+    if !text.details.is_empty() {
+        activity = activity.details(&text.details);
+    }
+    if !text.state.is_empty() {
+        activity = activity.state(&text.state);
+    }
 
+    if let Some(party) = queue_party(track, opts) {
+        activity = activity.party(party);
+    }
+
+    log_payload(&activity, opts);
+    client.set_activity(activity)?;
+    Ok(())
+}
+
+fn set_idle_activity(
+    client: &mut DiscordIpcClient,
+    text: &str,
+    opts: &ActivityOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let details = truncate_field(text, TEXT_FIELD_LIMIT, opts.truncation_style);
+    let assets = Assets::new()
+        .large_image(large_image(None, opts))
+        .large_text("Apple Music");
+    let activity = Activity::new().details(&details).assets(assets);
+
+    log_payload(&activity, opts);
     client.set_activity(activity)?;
     Ok(())
 }
 
-fn discord_thread_main(rx: mpsc::Receiver<DiscordCommand>, status: Arc<Mutex<DiscordStatus>>) {
+fn set_presence(
+    client: &mut DiscordIpcClient,
+    presence: &PendingPresence,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match presence {
+        PendingPresence::Track(track, art_url, false, opts) => {
+            set_activity_from_track(client, track, art_url.as_deref(), opts)
+        }
+        PendingPresence::Track(track, art_url, true, opts) => {
+            set_paused_activity(client, track, art_url.as_deref(), opts)
+        }
+        PendingPresence::Idle(text, opts) => set_idle_activity(client, text, opts),
+    }
+}
+
+/// Re-send the current presence (or the clear) so it doesn't expire. A playing track is
+/// moved on by `since` so its progress bar doesn't jump back.
+fn heartbeat(
+    client: &mut DiscordIpcClient,
+    presence: Option<&PendingPresence>,
+    since: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match presence {
+        Some(PendingPresence::Track(track, art_url, false, opts)) => {
+            let mut track = track.clone();
+            track.position_secs += since.as_secs_f64();
+            set_activity_from_track(client, &track, art_url.as_deref(), opts)
+        }
+        Some(presence) => set_presence(client, presence),
+        None => {
+            client.clear_activity()?;
+            Ok(())
+        }
+    }
+}
+
+/// Ping Discord. Only the write is checked: a socket Discord dropped fails it, while a
+/// read could block forever on one that is merely frozen.
+fn probe_socket(client: &mut DiscordIpcClient) -> Result<(), Box<dyn std::error::Error>> {
+    client.send(serde_json::json!({}), IPC_OPCODE_PING)?;
+    Ok(())
+}
+
+fn discord_thread_main(
+    rx: &mpsc::Receiver<DiscordCommand>,
+    status: SharedStatus,
+    replay: Option<PendingPresence>,
+    settings: &ThreadSettings,
+) {
     let mut client = DiscordIpcClient::new(DISCORD_APP_ID);
     let mut connected = false;
-    // Holds the last track so we can replay it after (re)connecting
-    let mut pending_track: Option<(TrackInfo, Option<String>, bool)> = None;
+    // Holds the last presence so we can replay it after (re)connecting
+    let mut pending_presence: Option<PendingPresence> = replay;
+    // Consecutive set_activity failures on a connected socket
+    let mut activity_failures: u32 = 0;
 
     // Initial connection attempt with backoff
     set_status(&status, DiscordStatus::Connecting);
@@ -204,20 +789,26 @@ fn discord_thread_main(rx: mpsc::Receiver<DiscordCommand>, status: Arc<Mutex<Dis
         );
         // Check for shutdown during backoff, but stash track updates
         match rx.recv_timeout(Duration::from_secs(delay)) {
-            Ok(DiscordCommand::Shutdown) => {
+            Ok(DiscordCommand::Shutdown(ack)) => {
                 set_status(&status, DiscordStatus::Disconnected);
+                if let Some(ack) = ack {
+                    let _ = ack.send(());
+                }
                 return;
             }
-            Ok(DiscordCommand::UpdateTrack(track, art_url, _)) => {
-                pending_track = Some((track, art_url, false));
+            Ok(DiscordCommand::UpdateTrack(track, art_url, opts)) => {
+                pending_presence = Some(PendingPresence::Track(track, art_url, false, opts));
+            }
+            Ok(DiscordCommand::SetPaused(track, art_url, opts)) => {
+                pending_presence = Some(PendingPresence::Track(track, art_url, true, opts));
             }
-            Ok(DiscordCommand::SetPaused(track, art_url, _)) => {
-                pending_track = Some((track, art_url, true));
+            Ok(DiscordCommand::SetIdle(text, opts)) => {
+                pending_presence = Some(PendingPresence::Idle(text, opts));
             }
             Ok(DiscordCommand::ClearPresence) => {
-                pending_track = None;
+                pending_presence = None;
             }
-            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Ok(DiscordCommand::Reconnect) | Err(mpsc::RecvTimeoutError::Timeout) => {}
             Err(mpsc::RecvTimeoutError::Disconnected) => {
                 set_status(&status, DiscordStatus::Disconnected);
                 return;
@@ -232,38 +823,48 @@ fn discord_thread_main(rx: mpsc::Receiver<DiscordCommand>, status: Arc<Mutex<Dis
 
     // Replay any track that arrived while we were connecting
     if connected {
-        if let Some((ref track, ref art_url, _paused)) = pending_track {
-            // On replay, use default opts since we don't store them
-            let opts = ActivityOptions {
-                show_timestamps: true,
-                show_album_art: true,
-                display_format: DisplayFormat::SongArtist,
-            };
-            if let Err(e) = set_activity_from_track(&mut client, track, art_url.as_deref(), &opts)
-            {
+        if let Some(ref presence) = pending_presence {
+            let result = set_presence(&mut client, presence);
+            if let Err(e) = result {
                 tracing::warn!("Failed to set initial Discord activity: {e}");
                 connected = false;
-                set_status(
-                    &status,
-                    DiscordStatus::Error(format!("Activity update failed: {e}")),
-                );
+                activity_failures += 1;
+                set_status(&status, activity_error_status(activity_failures, &e));
+            } else {
+                activity_failures = 0;
             }
         }
     }
 
     // Main event loop — with exponential backoff for reconnection
     let mut reconnect_backoff = Duration::from_secs(1);
+    // Failed reconnects in a row, and whether max_reconnect_attempts has run out
+    let mut reconnect_attempts: u32 = 0;
+    let mut gave_up = false;
+    // When the current presence was requested, and when the socket was last proven alive
+    let mut presence_since = Instant::now();
+    let mut last_heartbeat = Instant::now();
+    let mut last_liveness_check = Instant::now();
 
     loop {
         let timeout = if connected {
             Duration::from_secs(1)
+        } else if gave_up {
+            // Nothing to do until a command (e.g. Reconnect) arrives
+            Duration::from_secs(3600)
         } else {
             reconnect_backoff
         };
 
         match rx.recv_timeout(timeout) {
             Ok(DiscordCommand::UpdateTrack(track, art_url, opts)) => {
-                pending_track = Some((track.clone(), art_url.clone(), false));
+                pending_presence = Some(PendingPresence::Track(
+                    track.clone(),
+                    art_url.clone(),
+                    false,
+                    opts.clone(),
+                ));
+                presence_since = Instant::now();
                 if !connected {
                     continue;
                 }
@@ -272,14 +873,20 @@ fn discord_thread_main(rx: mpsc::Receiver<DiscordCommand>, status: Arc<Mutex<Dis
                 {
                     tracing::warn!("Failed to set Discord activity: {e}");
                     connected = false;
-                    set_status(
-                        &status,
-                        DiscordStatus::Error(format!("Activity update failed: {e}")),
-                    );
+                    activity_failures += 1;
+                    set_status(&status, activity_error_status(activity_failures, &e));
+                } else {
+                    activity_failures = 0;
                 }
             }
             Ok(DiscordCommand::SetPaused(track, art_url, opts)) => {
-                pending_track = Some((track.clone(), art_url.clone(), true));
+                pending_presence = Some(PendingPresence::Track(
+                    track.clone(),
+                    art_url.clone(),
+                    true,
+                    opts.clone(),
+                ));
+                presence_since = Instant::now();
                 if !connected {
                     continue;
                 }
@@ -288,64 +895,101 @@ fn discord_thread_main(rx: mpsc::Receiver<DiscordCommand>, status: Arc<Mutex<Dis
                 {
                     tracing::warn!("Failed to set paused Discord activity: {e}");
                     connected = false;
-                    set_status(
-                        &status,
-                        DiscordStatus::Error(format!("Activity update failed: {e}")),
-                    );
+                    activity_failures += 1;
+                    set_status(&status, activity_error_status(activity_failures, &e));
+                } else {
+                    activity_failures = 0;
+                }
+            }
+            Ok(DiscordCommand::SetIdle(text, opts)) => {
+                pending_presence = Some(PendingPresence::Idle(text.clone(), opts.clone()));
+                presence_since = Instant::now();
+                if !connected {
+                    continue;
+                }
+                if let Err(e) = set_idle_activity(&mut client, &text, &opts) {
+                    tracing::warn!("Failed to set idle Discord activity: {e}");
+                    connected = false;
+                    activity_failures += 1;
+                    set_status(&status, activity_error_status(activity_failures, &e));
+                } else {
+                    activity_failures = 0;
                 }
             }
             Ok(DiscordCommand::ClearPresence) => {
-                pending_track = None;
+                pending_presence = None;
+                presence_since = Instant::now();
                 if connected {
                     let _ = client.clear_activity();
                 }
             }
-            Ok(DiscordCommand::Shutdown) => {
+            Ok(DiscordCommand::Reconnect) => {
+                if gave_up {
+                    tracing::info!("Reconnecting to Discord on request");
+                    gave_up = false;
+                    reconnect_attempts = 0;
+                    reconnect_backoff = Duration::from_secs(1);
+                    set_status(&status, DiscordStatus::Disconnected);
+                }
+            }
+            Ok(DiscordCommand::Shutdown(ack)) => {
                 if connected {
                     let _ = client.clear_activity();
+                    // Give Discord a moment to read the clear before the socket goes away
+                    std::thread::sleep(SHUTDOWN_GRACE);
                     let _ = client.close();
                 }
                 set_status(&status, DiscordStatus::Disconnected);
+                if let Some(ack) = ack {
+                    let _ = ack.send(());
+                }
                 break;
             }
             Err(mpsc::RecvTimeoutError::Timeout) => {
+                if connected && last_liveness_check.elapsed() >= LIVENESS_CHECK_INTERVAL {
+                    last_liveness_check = Instant::now();
+                    if let Err(e) = probe_socket(&mut client) {
+                        tracing::warn!("Discord ping failed ({e}); socket looks stale");
+                        let _ = client.close();
+                        connected = false;
+                        set_status(&status, DiscordStatus::Disconnected);
+                    }
+                }
+
+                let heartbeat_secs = settings.heartbeat_secs.load(Ordering::Relaxed);
+                if connected
+                    && heartbeat_secs > 0
+                    && last_heartbeat.elapsed() >= Duration::from_secs(heartbeat_secs)
+                {
+                    last_heartbeat = Instant::now();
+                    let since = presence_since.elapsed();
+                    if let Err(e) = heartbeat(&mut client, pending_presence.as_ref(), since) {
+                        tracing::warn!("Discord heartbeat failed ({e}); socket looks stale");
+                        let _ = client.close();
+                        connected = false;
+                        set_status(&status, DiscordStatus::Disconnected);
+                    }
+                }
+
                 // If disconnected, try to reconnect with exponential backoff
-                if !connected {
+                if !connected && !gave_up {
                     set_status(&status, DiscordStatus::Connecting);
                     if try_connect(&mut client) {
                         connected = true;
                         reconnect_backoff = Duration::from_secs(1); // reset on success
+                        reconnect_attempts = 0;
                         set_status(&status, DiscordStatus::Connected);
                         tracing::info!("Discord IPC reconnected");
                         // Replay the last known track
-                        if let Some((ref track, ref art_url, paused)) = pending_track {
-                            let opts = ActivityOptions {
-                                show_timestamps: true,
-                                show_album_art: true,
-                                display_format: DisplayFormat::SongArtist,
-                            };
-                            let result = if paused {
-                                set_paused_activity(
-                                    &mut client,
-                                    track,
-                                    art_url.as_deref(),
-                                    &opts,
-                                )
-                            } else {
-                                set_activity_from_track(
-                                    &mut client,
-                                    track,
-                                    art_url.as_deref(),
-                                    &opts,
-                                )
-                            };
+                        if let Some(ref presence) = pending_presence {
+                            let result = set_presence(&mut client, presence);
                             if let Err(e) = result {
                                 tracing::warn!("Failed to replay Discord activity: {e}");
                                 connected = false;
-                                set_status(
-                                    &status,
-                                    DiscordStatus::Error(format!("Activity update failed: {e}")),
-                                );
+                                activity_failures += 1;
+                                set_status(&status, activity_error_status(activity_failures, &e));
+                            } else {
+                                activity_failures = 0;
                             }
                         }
                     } else {
@@ -353,6 +997,19 @@ fn discord_thread_main(rx: mpsc::Receiver<DiscordCommand>, status: Arc<Mutex<Dis
                         reconnect_backoff = (reconnect_backoff * 2).min(Duration::from_secs(30));
                         tracing::debug!("Discord reconnect failed, next attempt in {:?}", reconnect_backoff);
                         set_status(&status, DiscordStatus::Disconnected);
+
+                        reconnect_attempts += 1;
+                        let max = settings.max_reconnect_attempts.load(Ordering::Relaxed);
+                        if max > 0 && reconnect_attempts >= max {
+                            tracing::warn!(
+                                "Giving up on Discord after {reconnect_attempts} reconnect attempts"
+                            );
+                            gave_up = true;
+                            set_status(
+                                &status,
+                                DiscordStatus::Error("Gave up reconnecting".to_string()),
+                            );
+                        }
                     }
                 }
             }
@@ -368,3 +1025,59 @@ fn discord_thread_main(rx: mpsc::Receiver<DiscordCommand>, status: Arc<Mutex<Dis
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn middle_truncation_keeps_both_ends() {
+        assert_eq!(truncate_middle("abcdefghij", 5), "ab\u{2026}ij");
+        assert_eq!(truncate_middle("abcd efgh ijkl", 11), "abcd\u{2026}ijkl");
+    }
+
+    #[test]
+    fn middle_truncation_at_the_boundary() {
+        assert_eq!(truncate_middle("abcde", 5), "abcde");
+        assert_eq!(truncate_middle("abcdef", 5), "ab\u{2026}ef");
+        assert_eq!(truncate_middle("abcdef", 1), "\u{2026}");
+    }
+
+    #[test]
+    fn middle_truncation_is_multibyte_safe() {
+        assert_eq!(truncate_middle("ééééééé", 4), "éé\u{2026}é");
+        let cut = truncate_middle("日本語のタイトルです", 7);
+        assert_eq!(cut, "日本語\u{2026}ルです");
+        assert_eq!(cut.chars().count(), 7);
+    }
+
+    #[test]
+    fn end_truncation_is_multibyte_safe() {
+        assert_eq!(truncate("日本語のタイトル", 3), "日本語");
+        assert_eq!(
+            truncate_field("日本語のタイトル", 3, TruncationStyle::End),
+            "日本語"
+        );
+    }
+
+    #[test]
+    fn presence_text_fits_discord_limit_in_either_style() {
+        let long = format!("{}z", "a".repeat(200));
+        for style in [TruncationStyle::End, TruncationStyle::Middle] {
+            let text = fit_presence_text(
+                PresenceText {
+                    details: long.clone(),
+                    state: "by Artist".to_string(),
+                    large_text: long.clone(),
+                },
+                style,
+            );
+            assert_eq!(text.details.chars().count(), TEXT_FIELD_LIMIT);
+            assert_eq!(text.state, "by Artist");
+            assert_eq!(
+                text.details.ends_with('z'),
+                style == TruncationStyle::Middle
+            );
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::apple_music::TrackInfo;
+
+/// How long a hook may run before it's killed.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+const HOOK_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Run a user hook for `event` on its own thread, so a slow script never holds up
+/// polling. AppleScript files (`.scpt`, `.applescript`) go through `osascript`; anything
+/// else is executed directly. Track metadata is passed in `AMDP_*` environment variables.
+pub fn run_hook(script: &Path, event: &'static str, track: &TrackInfo) {
+    let is_applescript = script
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            ext.eq_ignore_ascii_case("scpt") || ext.eq_ignore_ascii_case("applescript")
+        });
+    let mut command = if is_applescript {
+        let mut command = Command::new("osascript");
+        command.arg(script);
+        command
+    } else {
+        Command::new(script)
+    };
+    command
+        .env("AMDP_EVENT", event)
+        .env("AMDP_TRACK_NAME", &track.name)
+        .env("AMDP_TRACK_ARTIST", &track.artist)
+        .env("AMDP_TRACK_ALBUM", &track.album)
+        .env("AMDP_TRACK_DURATION", format!("{:.0}", track.duration_secs))
+        .env("AMDP_TRACK_POSITION", format!("{:.0}", track.position_secs))
+        .env("AMDP_IS_PLAYING", track.is_playing.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    let script = script.display().to_string();
+    std::thread::spawn(move || {
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                tracing::warn!("Failed to run {event} hook {script}: {e}");
+                return;
+            }
+        };
+        let started = Instant::now();
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) if status.success() => {
+                    tracing::debug!("{event} hook {script} finished");
+                    return;
+                }
+                Ok(Some(status)) => {
+                    tracing::warn!("{event} hook {script} exited with {status}");
+                    return;
+                }
+                Ok(None) if started.elapsed() >= HOOK_TIMEOUT => {
+                    tracing::warn!(
+                        "{event} hook {script} still running after {}s; killing it",
+                        HOOK_TIMEOUT.as_secs()
+                    );
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return;
+                }
+                Ok(None) => std::thread::sleep(HOOK_POLL_INTERVAL),
+                Err(e) => {
+                    tracing::warn!("Failed to wait for {event} hook {script}: {e}");
+                    return;
+                }
+            }
+        }
+    });
+}
@@ -1,21 +1,35 @@
 mod album_art;
 mod apple_music;
+mod clipboard;
 mod commands;
 mod config;
 mod discord_rpc;
+mod hooks;
+mod power;
+mod source;
 mod state;
+mod template;
 mod tray;
 
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
-use config::{AppConfig, IdleBehavior};
+use apple_music::{AppleMusicError, TrackSourceKind};
+use config::{
+    AirPlayBehavior, AppConfig, IdleBehavior, IdleTrayIcon, MusicQuitBehavior, TimestampAnchor,
+    TruncationStyle,
+};
 use discord_rpc::{ActivityOptions, DiscordManager};
-use state::AppState;
+use serde::Serialize;
+use source::MusicSource;
+use state::{AppState, EffectivePollInterval, PollReason};
 use tauri::{ActivationPolicy, AppHandle, Emitter, Manager};
 use tauri_plugin_autostart::{MacosLauncher, ManagerExt};
 use tokio::time::{sleep, Duration};
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
 
 fn init_tracing() -> tracing_appender::non_blocking::WorkerGuard {
     let log_dir = dirs::home_dir()
@@ -35,10 +49,29 @@ fn init_tracing() -> tracing_appender::non_blocking::WorkerGuard {
     let env_filter = EnvFilter::try_from_env("AMDP_LOG")
         .unwrap_or_else(|_| EnvFilter::new("amdp=info"));
 
+    // AMDP_LOG_FORMAT=json switches the log file to JSON lines; the console stays readable
+    let json_logs = std::env::var("AMDP_LOG_FORMAT")
+        .map(|v| v.eq_ignore_ascii_case("json"))
+        .unwrap_or(false);
+
+    let file_layer = if json_logs {
+        fmt::layer()
+            .json()
+            .with_ansi(false)
+            .with_writer(non_blocking)
+            .boxed()
+    } else {
+        fmt::layer()
+            .with_target(false)
+            .with_ansi(false)
+            .with_writer(non_blocking)
+            .boxed()
+    };
+
     tracing_subscriber::registry()
         .with(env_filter)
         .with(fmt::layer().with_target(false))
-        .with(fmt::layer().with_target(false).with_ansi(false).with_writer(non_blocking))
+        .with(file_layer)
         .init();
 
     guard
@@ -69,14 +102,46 @@ fn cleanup_old_logs(log_dir: &std::path::Path, max_age_days: u64) {
     }
 }
 
-fn truncate_tray_label(text: &str, max_len: usize) -> String {
+/// Longest track/artist/album field written to the log before truncation.
+const LOG_FIELD_MAX_CHARS: usize = 80;
+
+/// Truncate `text` to at most `max_len` characters, ending in an ellipsis. With
+/// `at_word_boundary`, a cut that would split a word backs off to the previous
+/// whitespace, as long as that keeps at least half the available length.
+/// `TruncationStyle::Middle` cuts from the center instead and ignores word boundaries.
+fn truncate_tray_label(
+    text: &str,
+    max_len: usize,
+    at_word_boundary: bool,
+    style: TruncationStyle,
+) -> String {
     if text.chars().count() <= max_len {
         return text.to_string();
     }
-    let truncated: String = text.chars().take(max_len.saturating_sub(1)).collect();
+    if style == TruncationStyle::Middle {
+        return discord_rpc::truncate_middle(text, max_len);
+    }
+    let keep = max_len.saturating_sub(1);
+    let mut truncated: String = text.chars().take(keep).collect();
+    if at_word_boundary {
+        let cuts_word = text.chars().nth(keep).is_some_and(|c| !c.is_whitespace());
+        if cuts_word {
+            if let Some(idx) = truncated.rfind(char::is_whitespace) {
+                if truncated[..idx].chars().count() >= keep / 2 {
+                    truncated.truncate(idx);
+                }
+            }
+        }
+        truncated.truncate(truncated.trim_end().len());
+    }
     format!("{truncated}\u{2026}")
 }
 
+/// Shorten a metadata field for log output so huge titles don't blow out log lines.
+fn truncate_for_log(text: &str) -> String {
+    truncate_tray_label(text, LOG_FIELD_MAX_CHARS, false, TruncationStyle::End)
+}
+
 fn tracks_meaningfully_different(
     a: &Option<apple_music::TrackInfo>,
     b: &Option<apple_music::TrackInfo>,
@@ -89,6 +154,7 @@ fn tracks_meaningfully_different(
                 || a.artist != b.artist
                 || a.album != b.album
                 || a.is_playing != b.is_playing
+                || a.airplay_device != b.airplay_device
         }
     }
 }
@@ -99,120 +165,826 @@ fn read_config_snapshot(app_handle: &AppHandle) -> AppConfig {
     cfg
 }
 
-fn build_activity_options(cfg: &AppConfig) -> ActivityOptions {
+fn build_activity_options(
+    cfg: &AppConfig,
+    track_started_at: i64,
+    session_started_at: i64,
+) -> ActivityOptions {
     ActivityOptions {
-        show_timestamps: cfg.show_timestamps,
-        show_album_art: cfg.show_album_art,
+        show_timestamps: cfg.timestamps_enabled(),
+        session_started_at: (cfg.timestamp_anchor == TimestampAnchor::Session)
+            .then_some(session_started_at),
+        show_album_art: cfg.album_art_enabled(),
         display_format: cfg.display_format,
+        fallback_started_at: cfg.fallback_elapsed_timestamp.then_some(track_started_at),
+        show_queue_as_party: cfg.show_queue_as_party,
+        small_image_mode: cfg.small_image_mode,
+        show_quality_badge: cfg.show_quality_badge,
+        rating_stars: cfg.show_rating_stars.then_some(cfg.rating_source),
+        details_template: cfg.details_template().to_string(),
+        state_template: cfg.state_template().to_string(),
+        fixed_large_image: cfg.fixed_large_image_url.clone(),
+        truncation_style: cfg.truncation_style,
+        paused_hide_art: cfg.paused_hide_art,
+        show_version_in_presence: cfg.show_version_in_presence,
+        annotate_airplay: cfg.airplay_behavior == AirPlayBehavior::Annotate,
+        art_placement: cfg.art_placement,
+        log_payloads: cfg.log_discord_payloads
+            || std::env::var("AMDP_LOG_DISCORD_PAYLOADS").is_ok_and(|v| v == "1"),
+        new_badge_days: cfg.new_badge_days,
+        details_field: cfg.details_field,
+        state_field: cfg.state_field,
+    }
+}
+
+fn configure_art_resolver(resolver: &mut album_art::AlbumArtResolver, cfg: &AppConfig) {
+    resolver.set_min_art_bytes(cfg.reject_small_art.then_some(cfg.min_art_bytes));
+    resolver.set_cache_limits(cfg.max_memory_art_entries, cfg.max_disk_art_entries);
+    resolver.set_hide_explicit(cfg.hide_explicit_art);
+    resolver.set_verify_reachable(cfg.verify_art_reachable);
+    resolver.set_serve_stale_offline(cfg.offline_stale_art);
+    resolver.set_art_concurrency(cfg.art_concurrency);
+    resolver.set_itunes_result_limit(cfg.itunes_result_limit);
+}
+
+/// Payload of the `art-resolved` event; `url` is `None` on a miss.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ArtResolvedEvent<'a> {
+    key: &'a str,
+    url: Option<&'a str>,
+}
+
+/// Forward resolver progress as `art-resolving` / `art-resolved` events.
+fn emit_art_progress(app_handle: &AppHandle, progress: album_art::ArtProgress<'_>) {
+    let _ = match progress {
+        album_art::ArtProgress::Resolving { key } => app_handle.emit("art-resolving", key),
+        album_art::ArtProgress::Resolved { key, url } => {
+            app_handle.emit("art-resolved", ArtResolvedEvent { key, url })
+        }
+    };
+}
+
+/// Resolve artwork for `track`, waiting at most `art_wait_timeout_ms` (0 waits for the
+/// lookup to finish). On timeout the presence goes out with the logo and is re-sent
+/// with the cover once it arrives, as long as the same track is still current.
+async fn resolve_artwork(
+    app_handle: &AppHandle,
+    cfg: &AppConfig,
+    source: &dyn MusicSource,
+    track: &apple_music::TrackInfo,
+    track_started_at: i64,
+    session_started_at: i64,
+) -> Option<String> {
+    if !cfg.album_art_enabled() || cfg.fixed_large_image_url.is_some() {
+        return None;
+    }
+
+    let artist = album_art::query_artist(track).to_string();
+    if let Some(url) = source.artwork_url(track) {
+        let state = app_handle.state::<AppState>();
+        state
+            .art_resolver
+            .lock()
+            .await
+            .remember(&artist, &track.album, &track.name, &url);
+        return Some(url);
+    }
+    if !worth_itunes_lookup(track) {
+        tracing::debug!("Skipping iTunes artwork lookup for {:?} track", track.source_kind);
+        return None;
+    }
+
+    let handle = app_handle.clone();
+    let (album, name) = (track.album.clone(), track.name.clone());
+    let mut lookup = tokio::spawn(async move {
+        let state = handle.state::<AppState>();
+        let url = state.art_resolver.lock().await.resolve(&artist, &album, &name).await;
+        url
+    });
+
+    if cfg.art_wait_timeout_ms == 0 {
+        return lookup.await.ok().flatten();
+    }
+
+    let wait = Duration::from_millis(cfg.art_wait_timeout_ms);
+    match tokio::time::timeout(wait, &mut lookup).await {
+        Ok(joined) => joined.ok().flatten(),
+        Err(_) => {
+            tracing::debug!("Artwork lookup exceeded {wait:?}; presenting without art for now");
+            let handle = app_handle.clone();
+            let expected = track.clone();
+            tokio::spawn(async move {
+                if let Ok(Some(url)) = lookup.await {
+                    push_late_artwork(
+                        &handle,
+                        &expected,
+                        url,
+                        track_started_at,
+                        session_started_at,
+                    );
+                }
+            });
+            None
+        }
+    }
+}
+
+/// Streams have no album to look up, and local files missing their artist or album tags
+/// would only match somebody else's cover.
+fn worth_itunes_lookup(track: &apple_music::TrackInfo) -> bool {
+    match track.source_kind {
+        TrackSourceKind::Catalog => true,
+        TrackSourceKind::Local => !track.artist.trim().is_empty() && !track.album.trim().is_empty(),
+        TrackSourceKind::Stream => false,
+    }
+}
+
+fn push_late_artwork(
+    app_handle: &AppHandle,
+    expected: &apple_music::TrackInfo,
+    url: String,
+    track_started_at: i64,
+    session_started_at: i64,
+) {
+    let cfg = read_config_snapshot(app_handle);
+    if !cfg.enable_on_launch || !cfg.album_art_enabled() {
+        return;
+    }
+
+    let state = app_handle.state::<AppState>();
+    let Some(current) = state.current_track.lock().unwrap().clone() else {
+        return;
+    };
+    if current.name != expected.name
+        || current.artist != expected.artist
+        || current.album != expected.album
+    {
+        return;
+    }
+
+    tracing::debug!("Late artwork arrived for \"{}\"", truncate_for_log(&current.name));
+    *state.current_artwork_url.lock().unwrap() = Some(url.clone());
+    if state.manual_override.load(Ordering::Relaxed) {
+        return;
     }
+    if let Some(block) = presence_block(&state, &cfg, Some(&current)) {
+        tracing::debug!("Not pushing late artwork: {}", block.reason());
+        return;
+    }
+    let opts = build_activity_options(&cfg, track_started_at, session_started_at);
+    if current.is_playing {
+        state.push_presence(|discord| discord.update_track(&current, Some(url), opts));
+    } else if cfg.idle_behavior == IdleBehavior::ShowPaused {
+        state.push_presence(|discord| discord.set_paused(&current, Some(url), opts));
+    }
+}
+
+/// A repeat must land within this many seconds of the start of the track...
+const REPEAT_RESTART_MAX_POSITION_SECS: f64 = 5.0;
+/// ...after the previous read had played at least this fraction of it, so scrubbing
+/// back to the start early on isn't counted as a new play.
+const REPEAT_MIN_PROGRESS: f64 = 0.5;
+
+/// Whether playback of the same track jumped from late in the track back to its start.
+fn is_repeat_restart(last_position: f64, track: &apple_music::TrackInfo) -> bool {
+    track.duration_secs > 0.0
+        && track.position_secs < REPEAT_RESTART_MAX_POSITION_SECS
+        && last_position >= track.duration_secs * REPEAT_MIN_PROGRESS
+}
+
+/// How soon to re-read after an unconfirmed track change.
+const TRACK_CONFIRM_DELAY: Duration = Duration::from_secs(1);
+
+fn track_identity(track: &apple_music::TrackInfo) -> (String, String, String) {
+    (track.name.clone(), track.artist.clone(), track.album.clone())
+}
+
+/// Whether a read of a new track (`identity`, `None` when the track didn't change) still
+/// awaits a confirming read. The first read of an identity does and is remembered in
+/// `pending`; a second read agreeing with it confirms the change.
+fn awaiting_confirmation(
+    pending: &mut Option<(String, String, String)>,
+    identity: Option<(String, String, String)>,
+) -> bool {
+    match identity {
+        Some(identity) if pending.as_ref() != Some(&identity) => {
+            *pending = Some(identity);
+            true
+        }
+        _ => {
+            *pending = None;
+            false
+        }
+    }
+}
+
+/// Longest now-playing text shown next to the tray icon; the menu bar is shared space.
+const MENUBAR_TITLE_MAX_LEN: usize = 30;
+
+fn update_now_playing_label(
+    app_handle: &AppHandle,
+    result: &Option<apple_music::TrackInfo>,
+    cfg: &AppConfig,
+) {
+    let now_playing = result
+        .as_ref()
+        .map(|track| format!("{} \u{2014} {}", track.name, track.artist));
+    let truncate = |text: &str, max_len: usize| {
+        truncate_tray_label(
+            text,
+            max_len,
+            cfg.truncate_tray_at_word_boundary,
+            cfg.truncation_style,
+        )
+    };
+
+    // Paused tracks get a pause sign so the title alone shows the play state
+    let title = now_playing.as_deref().filter(|_| cfg.show_track_in_menubar).map(|text| {
+        let title = truncate(text, MENUBAR_TITLE_MAX_LEN);
+        if result.as_ref().is_some_and(|t| t.is_playing) {
+            title
+        } else {
+            format!("\u{23F8} {title}")
+        }
+    });
+    tray::set_tray_title(app_handle, title.as_deref());
+
+    let state = app_handle.state::<AppState>();
+    let guard = state.now_playing_item.lock().unwrap();
+    if let Some(item) = guard.as_ref() {
+        let label = match &now_playing {
+            Some(full) => truncate(full, 50),
+            None => "Not Playing".to_string(),
+        };
+        let _ = item.set_text(label);
+        let _ = item.set_enabled(tray::now_playing_item_enabled(
+            cfg.now_playing_click_action,
+            result.is_some(),
+        ));
+    }
+}
+
+/// Refresh the tray progress item; called on every poll as the position moves.
+fn update_progress_label(
+    app_handle: &AppHandle,
+    result: &Option<apple_music::TrackInfo>,
+    cfg: &AppConfig,
+) {
+    let state = app_handle.state::<AppState>();
+    let guard = state.progress_item.lock().unwrap();
+    if let Some(item) = guard.as_ref() {
+        let label = match result {
+            Some(track) => tray::progress_label(
+                track.position_secs,
+                track.duration_secs,
+                cfg.tray_progress_style,
+            ),
+            None => tray::NO_PROGRESS_LABEL.to_string(),
+        };
+        let _ = item.set_text(label);
+    }
+}
+
+/// Most recent tracks kept in `AppState::track_history`.
+const TRACK_HISTORY_LIMIT: usize = 50;
+
+fn record_history(state: &AppState, track: &apple_music::TrackInfo) {
+    let mut history = state.track_history.lock().unwrap();
+    history.push_front(track.clone());
+    history.truncate(TRACK_HISTORY_LIMIT);
+}
+
+/// A rule that keeps the current track off Discord. Checked by the poll loop and by
+/// anything else that pushes presence for the current track (e.g. `refresh_current_art`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PresenceBlock {
+    /// `auto_stop_after_hours` ran out; presence was cleared when it did.
+    AutoStopped,
+    AirPlay,
+    LocalFile,
+    Playlist,
+    /// Interludes and skits below `min_track_duration_secs`.
+    TooShort,
+}
+
+impl PresenceBlock {
+    /// Whether presence is cleared; otherwise the current presence is left alone.
+    fn clears(self) -> bool {
+        matches!(self, Self::AirPlay | Self::LocalFile | Self::Playlist)
+    }
+
+    fn reason(self) -> &'static str {
+        match self {
+            Self::AutoStopped => "Presence auto-stopped; leaving presence cleared",
+            Self::AirPlay => "Playing over AirPlay; presence suppressed",
+            Self::LocalFile => "Local file playing; presence hidden",
+            Self::Playlist => "Playlist not in broadcast_only_playlists; clearing",
+            Self::TooShort => "Not broadcasting short track",
+        }
+    }
+}
+
+/// The first rule keeping `track` (`None` when nothing is playing) off Discord, if any.
+fn presence_block(
+    state: &AppState,
+    cfg: &AppConfig,
+    track: Option<&apple_music::TrackInfo>,
+) -> Option<PresenceBlock> {
+    if state.auto_stopped.load(Ordering::Relaxed) {
+        return Some(PresenceBlock::AutoStopped);
+    }
+    let track = track?;
+    if cfg.airplay_behavior == AirPlayBehavior::Suppress && track.airplay_device.is_some() {
+        Some(PresenceBlock::AirPlay)
+    } else if cfg.hide_local_files && track.source_kind == TrackSourceKind::Local {
+        Some(PresenceBlock::LocalFile)
+    } else if !is_broadcast_playlist(track, cfg) {
+        Some(PresenceBlock::Playlist)
+    } else if is_below_min_duration(track, cfg) {
+        Some(PresenceBlock::TooShort)
+    } else {
+        None
+    }
+}
+
+/// Tracks shorter than `min_track_duration_secs` aren't broadcast. Streams and other
+/// tracks without a known duration always are.
+fn is_below_min_duration(track: &apple_music::TrackInfo, cfg: &AppConfig) -> bool {
+    cfg.min_track_duration_secs > 0
+        && track.duration_secs > 0.0
+        && track.duration_secs < cfg.min_track_duration_secs as f64
+}
+
+/// Whether `track` is playing from a playlist in `broadcast_only_playlists`. An empty
+/// list allows everything; a track with no known playlist is then never broadcast.
+fn is_broadcast_playlist(track: &apple_music::TrackInfo, cfg: &AppConfig) -> bool {
+    if cfg.broadcast_only_playlists.is_empty() {
+        return true;
+    }
+    track.playlist_name.as_deref().is_some_and(|playlist| {
+        cfg.broadcast_only_playlists
+            .iter()
+            .any(|allowed| allowed.trim().eq_ignore_ascii_case(playlist.trim()))
+    })
+}
+
+/// Shortest poll interval regardless of configuration, so `osascript` is never spawned
+/// fast enough to saturate the CPU.
+const MIN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Poll interval for the last known player state. The per-state intervals fall back
+/// to `poll_interval_secs` when unset.
+fn poll_interval(cfg: &AppConfig, is_playing: bool) -> Duration {
+    // Minimal mode always polls at the slower paused rate
+    let secs = if is_playing && !cfg.minimal_mode {
+        cfg.poll_interval_playing_secs
+    } else {
+        cfg.poll_interval_paused_secs
+    };
+    Duration::from_secs(secs.unwrap_or(cfg.poll_interval_secs)).max(MIN_POLL_INTERVAL)
+}
+
+fn poll_reason(cfg: &AppConfig, is_playing: bool) -> PollReason {
+    if cfg.minimal_mode {
+        PollReason::Minimal
+    } else if is_playing {
+        PollReason::Playing
+    } else {
+        PollReason::Paused
+    }
+}
+
+/// Record the interval the poll loop is about to sleep for `get_effective_poll_interval`.
+fn publish_poll_interval(app_handle: &AppHandle, interval: Duration, reason: PollReason) {
+    let state = app_handle.state::<AppState>();
+    *state.effective_poll_interval.lock().unwrap() = Some(EffectivePollInterval {
+        interval_secs: interval.as_secs_f64(),
+        reason,
+    });
+}
+
+/// A poll sleep that overran its interval by more than the slack means the system slept.
+/// The slack is the configured floor or half the interval, whichever is larger, so long
+/// intervals tolerate proportionally more scheduling jitter.
+fn is_wake_from_sleep(elapsed: Duration, expected: Duration, slack_secs: u64) -> bool {
+    let slack = Duration::from_secs(slack_secs).max(expected / 2);
+    elapsed > expected + slack
+}
+
+/// How often the power source is re-read while `pause_art_on_battery` is on.
+const POWER_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often the background art refresh looks for an expiring entry.
+const ART_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+/// Most art cache entries refreshed per session.
+const ART_REFRESH_SESSION_LIMIT: usize = 50;
+
+/// While nothing is playing, re-fetch art cache entries close to their TTL one at a
+/// time, so covers for albums played regularly don't expire right as they start.
+fn start_art_refresh(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut attempted: HashSet<String> = HashSet::new();
+        while attempted.len() < ART_REFRESH_SESSION_LIMIT {
+            sleep(ART_REFRESH_INTERVAL).await;
+
+            let cfg = read_config_snapshot(&app_handle);
+            let state = app_handle.state::<AppState>();
+            let playing = state
+                .current_track
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_some_and(|t| t.is_playing);
+            if !cfg.refresh_expiring_art || cfg.minimal_mode || playing {
+                continue;
+            }
+
+            // One entry per lock so foreground resolves wait at most one lookup
+            let mut resolver = state.art_resolver.lock().await;
+            let Some(key) = resolver.next_expiring_key(&attempted) else {
+                continue;
+            };
+            resolver.refresh_entry(&key).await;
+            attempted.insert(key);
+        }
+        tracing::debug!("Art refresh session limit reached");
+    });
+}
+
+/// Emit `track-changed`, running the track-change `hook` first if there is one. With
+/// `track_event_min_interval_secs` set, both wait until that long has passed without a
+/// newer change, so a burst of skips only sends the last track.
+fn emit_track_changed(
+    app_handle: &AppHandle,
+    cfg: &AppConfig,
+    generation: &Arc<AtomicU64>,
+    track: Option<apple_music::TrackInfo>,
+    hook: Option<PathBuf>,
+) {
+    let send = move |app_handle: &AppHandle| {
+        if let (Some(script), Some(track)) = (&hook, &track) {
+            hooks::run_hook(script, "trackChange", track);
+        }
+        let _ = app_handle.emit("track-changed", &track);
+    };
+    let current = generation.fetch_add(1, Ordering::Relaxed) + 1;
+    if cfg.track_event_min_interval_secs == 0 {
+        send(app_handle);
+        return;
+    }
+
+    let delay = Duration::from_secs(cfg.track_event_min_interval_secs);
+    let (handle, generation) = (app_handle.clone(), Arc::clone(generation));
+    tauri::async_runtime::spawn(async move {
+        sleep(delay).await;
+        if generation.load(Ordering::Relaxed) == current {
+            send(&handle);
+        }
+    });
 }
 
 fn start_polling(app_handle: AppHandle) {
     tauri::async_runtime::spawn(async move {
         let mut previous: Option<apple_music::TrackInfo> = None;
-        let mut art_resolver = album_art::AlbumArtResolver::new();
-        let mut last_poll = Instant::now();
+        // Unix time the current track was first seen, for the elapsed-timer fallback
+        let mut track_started_at = discord_rpc::now_secs();
+        // Unix time the listening session started and when playback last stopped, for
+        // TimestampAnchor::Session
+        let mut session_started_at = track_started_at;
+        let mut session_stopped_at: Option<Instant> = None;
+        // Last real track read and when Music.app was seen quitting, for music_quit_behavior
+        let mut last_seen_track: Option<apple_music::TrackInfo> = None;
+        let mut music_quit_at: Option<Instant> = None;
+        // Identity of a new track seen once and awaiting a confirming read
+        let mut pending_identity: Option<(String, String, String)> = None;
+        let mut tray_idle: Option<bool> = None;
+        let source: Arc<dyn MusicSource> = Arc::new(apple_music::AppleMusicSource);
+        tracing::info!("Reading tracks from {}", source.name());
+        // Position from the previous read, for repeat-one detection
+        let mut last_position: Option<f64> = None;
+        // Continuous playback start, for auto_stop_after_hours
+        let mut playing_since: Option<Instant> = None;
+        // idle_presence text last handled, so a newly set message goes out without a change
+        let mut shown_idle_presence: Option<String> = None;
+        // Last power source read for pause_art_on_battery, and when
+        let mut on_battery = false;
+        let mut power_checked_at: Option<Instant> = None;
+        // Bumped per track-changed event so a debounced emit knows it was superseded
+        let track_event_generation = Arc::new(AtomicU64::new(0));
 
         loop {
             let cfg = read_config_snapshot(&app_handle);
-            sleep(Duration::from_secs(cfg.poll_interval_secs)).await;
 
-            // Sleep/wake detection
-            let elapsed = last_poll.elapsed();
-            let expected = Duration::from_secs(cfg.poll_interval_secs);
-            if elapsed > expected + Duration::from_secs(10) {
-                tracing::info!(
-                    "System wake detected (elapsed {:.1}s, expected {:.1}s) — forcing re-sync",
-                    elapsed.as_secs_f64(),
-                    expected.as_secs_f64()
-                );
+            if !app_handle
+                .state::<AppState>()
+                .polling_enabled
+                .load(Ordering::Relaxed)
+            {
+                let is_playing = previous.as_ref().is_some_and(|t| t.is_playing);
+                let interval = poll_interval(&cfg, is_playing);
+                publish_poll_interval(&app_handle, interval, PollReason::PollingPaused);
+                sleep(interval).await;
+                continue;
+            }
+
+            if app_handle
+                .state::<AppState>()
+                .force_resync
+                .swap(false, Ordering::Relaxed)
+            {
                 previous = None;
             }
-            last_poll = Instant::now();
 
-            let result = tokio::task::spawn_blocking(apple_music::get_current_track)
+            let due = power_checked_at.is_none_or(|at| at.elapsed() >= POWER_CHECK_INTERVAL);
+            if cfg.pause_art_on_battery && due {
+                power_checked_at = Some(Instant::now());
+                // Fail open: do lookups when the power source can't be read
+                let battery = tokio::task::spawn_blocking(power::on_battery)
+                    .await
+                    .ok()
+                    .flatten()
+                    .unwrap_or(false);
+                if battery != on_battery {
+                    if battery {
+                        tracing::info!("On battery; pausing artwork lookups");
+                    } else {
+                        tracing::info!("On AC power; resuming artwork lookups");
+                    }
+                    on_battery = battery;
+                    let state = app_handle.state::<AppState>();
+                    state.art_resolver.lock().await.set_cache_only(on_battery);
+                }
+            } else if !cfg.pause_art_on_battery && on_battery {
+                on_battery = false;
+                power_checked_at = None;
+                let state = app_handle.state::<AppState>();
+                state.art_resolver.lock().await.set_cache_only(false);
+            }
+
+            let reader = Arc::clone(&source);
+            let fetched = tokio::task::spawn_blocking(move || reader.current_track())
                 .await
-                .ok()
-                .and_then(|r| r.ok());
+                .ok();
+            if matches!(fetched, Some(Err(AppleMusicError::Busy))) {
+                // Another read (e.g. a latency measurement) is still running; its result
+                // isn't ours, so keep the current state and try again next interval
+                tracing::info!("Skipping poll: previous track read still in flight");
+                let is_playing = previous.as_ref().is_some_and(|t| t.is_playing);
+                let interval = poll_interval(&cfg, is_playing);
+                publish_poll_interval(&app_handle, interval, poll_reason(&cfg, is_playing));
+                sleep(interval).await;
+                continue;
+            }
+            let music_quit = matches!(fetched, Some(Err(AppleMusicError::AppNotRunning)));
+            let mut result = fetched.and_then(|r| r.ok());
+
+            // Optionally let the last track linger as paused while Music.app is quit
+            let mut lingering_after_quit = false;
+            if result.is_some() {
+                last_seen_track = result.clone();
+                music_quit_at = None;
+            } else if music_quit && cfg.music_quit_behavior == MusicQuitBehavior::ShowLastPaused {
+                let quit_at = *music_quit_at.get_or_insert_with(Instant::now);
+                if quit_at.elapsed() < Duration::from_secs(cfg.music_quit_grace_secs) {
+                    result = last_seen_track.clone().map(|mut track| {
+                        track.is_playing = false;
+                        track
+                    });
+                    lingering_after_quit = result.is_some();
+                }
+            } else {
+                music_quit_at = None;
+            }
 
-            tracing::debug!("Poll result: {:?}", result.as_ref().map(|t| &t.name));
+            tracing::debug!(
+                "Poll result: {:?}",
+                result.as_ref().map(|t| truncate_for_log(&t.name))
+            );
 
             let changed = tracks_meaningfully_different(&previous, &result);
 
+            let same_track = match (&previous, &result) {
+                (Some(a), Some(b)) => a.name == b.name && a.artist == b.artist && a.album == b.album,
+                _ => false,
+            };
+
+            // Gapless transitions can be read mid-update (new name, old album). A new
+            // track must read the same on two consecutive polls before it's pushed to
+            // Discord; the tray label updates right away.
+            let new_identity = result
+                .as_ref()
+                .filter(|_| changed && !same_track)
+                .map(track_identity);
+            let unconfirmed = awaiting_confirmation(&mut pending_identity, new_identity);
+            let changed = changed && !unconfirmed;
+
+            // Repeat-one never changes the metadata; catch the jump back to the start
+            let repeated = same_track
+                && !changed
+                && result.as_ref().is_some_and(|track| {
+                    track.is_playing
+                        && last_position.is_some_and(|pos| is_repeat_restart(pos, track))
+                });
+            if repeated {
+                tracing::info!("Track restarted (repeat)");
+            }
+            // An idle presence is (re)sent when first configured, not only on a change
+            let idle_presence_stale = result.is_none() && cfg.idle_presence != shown_idle_presence;
+            let changed = changed || repeated || idle_presence_stale;
+
+            // auto_stop_after_hours: the clock restarts when the user picks or skips to a
+            // track (a change before the previous one reached its end), pauses, or turns
+            // presence off; natural playlist advances and repeats keep it running.
+            let user_changed_track = changed
+                && !same_track
+                && match (&previous, last_position) {
+                    (Some(prev), Some(pos)) => {
+                        let end_slack = poll_interval(&cfg, true).as_secs_f64() + 5.0;
+                        prev.duration_secs <= 0.0 || pos < prev.duration_secs - end_slack
+                    }
+                    _ => true,
+                };
+            last_position = result.as_ref().map(|t| t.position_secs);
+            let playing_now = result.as_ref().is_some_and(|t| t.is_playing);
+            if !cfg.enable_on_launch || !playing_now || user_changed_track {
+                let state = app_handle.state::<AppState>();
+                if state.auto_stopped.swap(false, Ordering::Relaxed) {
+                    tracing::info!("Resuming presence after auto-stop");
+                    state.force_resync.store(true, Ordering::Relaxed);
+                }
+                playing_since = None;
+            }
+            if playing_now {
+                let gap = Duration::from_secs(cfg.session_gap_secs);
+                if session_stopped_at.take().is_some_and(|at| at.elapsed() >= gap) {
+                    tracing::debug!("Starting a new listening session");
+                    session_started_at = discord_rpc::now_secs();
+                }
+                let since = *playing_since.get_or_insert_with(Instant::now);
+                if let Some(hours) = cfg.auto_stop_after_hours {
+                    let state = app_handle.state::<AppState>();
+                    if !state.auto_stopped.load(Ordering::Relaxed)
+                        && since.elapsed() >= Duration::from_secs(hours * 3600)
+                    {
+                        tracing::info!("Playing for over {hours}h; stopping presence");
+                        state.discord.clear_presence();
+                        state.auto_stopped.store(true, Ordering::Relaxed);
+                    }
+                }
+            } else if session_stopped_at.is_none() {
+                session_stopped_at = Some(Instant::now());
+            }
+
+            if changed && (!same_track || repeated) {
+                track_started_at = discord_rpc::now_secs();
+                if let Some(track) = &result {
+                    record_history(&app_handle.state::<AppState>(), track);
+                }
+            }
+
             // Always update state with latest info
             {
                 let state = app_handle.state::<AppState>();
                 let mut current = state.current_track.lock().unwrap();
                 *current = result.clone();
+                if result.is_none() {
+                    *state.current_artwork_url.lock().unwrap() = None;
+                }
+            }
+
+            if unconfirmed {
+                update_now_playing_label(&app_handle, &result, &cfg);
+            }
+            update_progress_label(&app_handle, &result, &cfg);
+
+            // Swap the tray icon only on idle/playing transitions
+            let idle = cfg.idle_tray_icon == IdleTrayIcon::Dimmed
+                && !result.as_ref().is_some_and(|t| t.is_playing);
+            if tray_idle != Some(idle) {
+                tray::set_tray_idle(&app_handle, idle);
+                tray_idle = Some(idle);
             }
 
             if changed {
                 if let Some(ref track) = result {
                     tracing::info!(
                         "Track changed: \"{}\" by {} ({})",
-                        track.name,
-                        track.artist,
+                        truncate_for_log(&track.name),
+                        truncate_for_log(&track.artist),
                         if track.is_playing { "playing" } else { "paused" }
                     );
                 } else {
                     tracing::info!("Track changed: nothing playing");
                 }
 
-                // Update tray now-playing label
-                {
-                    let state = app_handle.state::<AppState>();
-                    let guard = state.now_playing_item.lock().unwrap();
-                    if let Some(item) = guard.as_ref() {
-                        let label = match &result {
-                            Some(track) => {
-                                let full = format!("{} \u{2014} {}", track.name, track.artist);
-                                truncate_tray_label(&full, 50)
-                            }
-                            None => "Not Playing".to_string(),
-                        };
-                        let _ = item.set_text(label);
+                update_now_playing_label(&app_handle, &result, &cfg);
+
+                // The track-change hook goes out with the (possibly debounced) event
+                let mut track_change_hook = None;
+                if let Some(track) = &result {
+                    let was_playing = previous.as_ref().is_some_and(|t| t.is_playing);
+                    if track.is_playing && (!same_track || repeated) {
+                        track_change_hook = cfg.on_track_change_script.clone();
+                    } else if !track.is_playing && same_track && was_playing {
+                        if let Some(script) = &cfg.on_pause_script {
+                            hooks::run_hook(script, "pause", track);
+                        }
                     }
-                    drop(guard);
                 }
 
                 // Re-read config for Discord decisions
                 let cfg = read_config_snapshot(&app_handle);
                 let presence_enabled = cfg.enable_on_launch;
 
-                if presence_enabled {
+                // A manual override holds until real playback resumes
+                let override_active = {
+                    let state = app_handle.state::<AppState>();
+                    let active = state.manual_override.load(Ordering::Relaxed);
+                    if active && result.as_ref().is_some_and(|t| t.is_playing) {
+                        tracing::info!("Playback resumed; clearing manual presence override");
+                        state.manual_override.store(false, Ordering::Relaxed);
+                        false
+                    } else {
+                        active
+                    }
+                };
+
+                let block =
+                    presence_block(&app_handle.state::<AppState>(), &cfg, result.as_ref());
+                if override_active {
+                    tracing::debug!("Manual presence override active; leaving presence alone");
+                } else if let Some(block) = block {
+                    tracing::debug!("{}", block.reason());
+                    if block.clears() {
+                        app_handle.state::<AppState>().discord.clear_presence();
+                    }
+                } else if presence_enabled {
                     let state = app_handle.state::<AppState>();
                     match &result {
                         Some(track) if track.is_playing => {
-                            let artwork_url = if cfg.show_album_art {
-                                art_resolver.resolve(&track.artist, &track.album).await
-                            } else {
-                                None
-                            };
-                            let opts = build_activity_options(&cfg);
-                            state.discord.update_track(track, artwork_url, opts);
+                            let artwork_url = resolve_artwork(
+                                &app_handle,
+                                &cfg,
+                                source.as_ref(),
+                                track,
+                                track_started_at,
+                                session_started_at,
+                            )
+                            .await;
+                            *state.current_artwork_url.lock().unwrap() = artwork_url.clone();
+                            let opts =
+                                build_activity_options(&cfg, track_started_at, session_started_at);
+                            state.push_presence(|discord| {
+                                discord.update_track(track, artwork_url, opts)
+                            });
                         }
                         Some(track) => {
                             // Paused
-                            match cfg.idle_behavior {
+                            // A track lingering after Music.app quit is always shown paused
+                            let idle_behavior = if lingering_after_quit {
+                                IdleBehavior::ShowPaused
+                            } else {
+                                cfg.idle_behavior
+                            };
+                            match idle_behavior {
                                 IdleBehavior::ClearStatus => {
                                     state.discord.clear_presence();
                                 }
                                 IdleBehavior::ShowPaused => {
-                                    let artwork_url = if cfg.show_album_art {
-                                        art_resolver
-                                            .resolve(&track.artist, &track.album)
-                                            .await
-                                    } else {
-                                        None
-                                    };
-                                    let opts = build_activity_options(&cfg);
-                                    state.discord.set_paused(track, artwork_url, opts);
+                                    let artwork_url = resolve_artwork(
+                                        &app_handle,
+                                        &cfg,
+                                        source.as_ref(),
+                                        track,
+                                        track_started_at,
+                                        session_started_at,
+                                    )
+                                    .await;
+                                    *state.current_artwork_url.lock().unwrap() =
+                                        artwork_url.clone();
+                                    let opts = build_activity_options(
+                                        &cfg,
+                                        track_started_at,
+                                        session_started_at,
+                                    );
+                                    state.push_presence(|discord| {
+                                        discord.set_paused(track, artwork_url, opts)
+                                    });
                                 }
                             }
                         }
-                        None => {
-                            state.discord.clear_presence();
-                        }
+                        None => match &cfg.idle_presence {
+                            Some(text) => {
+                                let opts = build_activity_options(
+                                    &cfg,
+                                    track_started_at,
+                                    session_started_at,
+                                );
+                                state.push_presence(|discord| discord.set_idle(text, opts));
+                            }
+                            None => state.discord.clear_presence(),
+                        },
                     }
                 } else {
                     // Presence disabled — ensure cleared
@@ -220,9 +992,42 @@ fn start_polling(app_handle: AppHandle) {
                     state.discord.clear_presence();
                 }
 
-                let _ = app_handle.emit("track-changed", &result);
+                shown_idle_presence = if result.is_none() {
+                    cfg.idle_presence.clone()
+                } else {
+                    None
+                };
+                emit_track_changed(
+                    &app_handle,
+                    &cfg,
+                    &track_event_generation,
+                    result.clone(),
+                    track_change_hook,
+                );
                 previous = result;
             }
+
+            // Sleep at the end of the iteration so a wake (or startup) polls right away
+            let is_playing = previous.as_ref().is_some_and(|t| t.is_playing);
+            let (expected, reason) = if unconfirmed {
+                (TRACK_CONFIRM_DELAY, PollReason::Confirming)
+            } else {
+                (poll_interval(&cfg, is_playing), poll_reason(&cfg, is_playing))
+            };
+            publish_poll_interval(&app_handle, expected, reason);
+            let sleep_started = Instant::now();
+            sleep(expected).await;
+
+            // Sleep/wake detection
+            let elapsed = sleep_started.elapsed();
+            if is_wake_from_sleep(elapsed, expected, cfg.wake_detection_slack_secs) {
+                tracing::info!(
+                    "System wake detected (elapsed {:.1}s, expected {:.1}s) — forcing re-sync",
+                    elapsed.as_secs_f64(),
+                    expected.as_secs_f64()
+                );
+                previous = None;
+            }
         }
     });
 }
@@ -233,23 +1038,66 @@ pub fn run() {
 
     tracing::info!("AMDP starting up");
 
-    let discord = DiscordManager::start();
+    let safe_mode = std::env::var("AMDP_SAFE_MODE").is_ok_and(|v| v == "1");
+    if safe_mode {
+        tracing::warn!("==============================================================");
+        tracing::warn!("SAFE MODE ACTIVE (AMDP_SAFE_MODE=1): Discord, album art lookups");
+        tracing::warn!("and update checks are disabled. Polling and tray still run.");
+        tracing::warn!("==============================================================");
+    }
+
+    let discord = if safe_mode {
+        DiscordManager::disabled()
+    } else {
+        DiscordManager::start()
+    };
     let loaded_config = config::load_config();
+    discord.set_heartbeat_interval(loaded_config.presence_heartbeat_secs);
+    discord.set_max_reconnect_attempts(loaded_config.max_reconnect_attempts);
     let config = Arc::new(Mutex::new(loaded_config));
 
     tauri::Builder::default()
+        // Must be registered first: a second launch hands off to the running instance
+        // and exits before anything else is set up
+        .plugin(tauri_plugin_single_instance::init(|app, _argv, _cwd| {
+            tracing::info!("Another AMDP launch detected; showing Settings");
+            tray::show_settings(app);
+        }))
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_autostart::init(
             MacosLauncher::LaunchAgent,
             None,
         ))
         .plugin(tauri_plugin_updater::Builder::new().build())
-        .manage(AppState::new(discord, config))
+        .manage(AppState::new(discord, config, safe_mode))
         .invoke_handler(tauri::generate_handler![
             commands::get_current_track,
             commands::get_discord_status,
+            commands::get_effective_poll_interval,
+            commands::verify_discord_assets,
+            commands::reconnect_discord,
             commands::get_config,
             commands::save_config,
+            commands::copy_now_playing,
+            commands::get_cache_stats,
+            commands::export_art_cache,
+            commands::import_art_cache,
+            commands::get_raw_track_output,
+            commands::get_update_status,
+            commands::get_track_history,
+            commands::set_presence_from_history,
+            commands::dump_diagnostics,
+            commands::copy_diagnostics,
+            commands::set_polling_enabled,
+            commands::validate_template,
+            commands::check_truncation,
+            commands::get_config_diff,
+            commands::refresh_current_art,
+            commands::measure_poll_latency,
+            #[cfg(feature = "debug_commands")]
+            commands::inject_track,
+            #[cfg(feature = "debug_commands")]
+            commands::debug_set_discord_status,
         ])
         .on_window_event(|window, event| {
             if let tauri::WindowEvent::CloseRequested { api, .. } = event {
@@ -273,21 +1121,91 @@ pub fn run() {
                 let _ = autolaunch.disable();
             }
 
-            // Delayed update check (10 seconds after launch)
+            // Delayed update check, optionally repeating on an interval
             let app_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
-                tokio::time::sleep(Duration::from_secs(10)).await;
-                check_for_updates(app_handle).await;
+                if app_handle.state::<AppState>().safe_mode {
+                    return;
+                }
+                let cfg = read_config_snapshot(&app_handle);
+                sleep(Duration::from_secs(cfg.update_check_delay_secs)).await;
+                loop {
+                    let cfg = read_config_snapshot(&app_handle);
+                    let succeeded = if cfg.auto_update_check {
+                        check_for_updates_with_retry(&app_handle, cfg.update_check_retries).await
+                    } else {
+                        tracing::info!("Automatic update check disabled");
+                        true
+                    };
+                    let next_check = match (succeeded, cfg.update_check_interval_secs) {
+                        (false, interval) if cfg.update_failure_recheck_secs > 0 => {
+                            let recheck = cfg.update_failure_recheck_secs;
+                            tracing::info!("Scheduling update re-check in {recheck}s");
+                            if interval == 0 {
+                                recheck
+                            } else {
+                                interval.min(recheck)
+                            }
+                        }
+                        (_, 0) => break,
+                        (_, interval) => interval,
+                    };
+                    sleep(Duration::from_secs(next_check)).await;
+                }
             });
 
+            let progress_handle = app.handle().clone();
+            state
+                .art_resolver
+                .blocking_lock()
+                .set_progress_listener(Box::new(move |progress: album_art::ArtProgress<'_>| {
+                    emit_art_progress(&progress_handle, progress)
+                }));
+
             start_polling(app.handle().clone());
+            start_art_refresh(app.handle().clone());
             Ok(())
         })
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
 
-async fn check_for_updates(app: AppHandle) {
+/// Delay before the first retry of a failed update check; doubles for each retry after.
+const UPDATE_RETRY_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Run the update check, retrying up to `retries` times. Returns whether a check
+/// completed; the last error is kept for `get_update_status`.
+async fn check_for_updates_with_retry(app: &AppHandle, retries: u32) -> bool {
+    let mut backoff = UPDATE_RETRY_BACKOFF;
+    let mut attempt = 0;
+    let result = loop {
+        match check_for_updates(app.clone()).await {
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                tracing::info!("Retrying update check in {}s ({e})", backoff.as_secs());
+                sleep(backoff).await;
+                backoff *= 2;
+            }
+            result => break result,
+        }
+    };
+
+    let state = app.state::<AppState>();
+    match result {
+        Ok(()) => {
+            *state.update_check_error.lock().unwrap() = None;
+            true
+        }
+        Err(e) => {
+            tracing::warn!("Update check failed after {} attempt(s)", attempt + 1);
+            *state.update_check_error.lock().unwrap() = Some(e.clone());
+            let _ = app.emit("update-check-failed", e);
+            false
+        }
+    }
+}
+
+async fn check_for_updates(app: AppHandle) -> Result<(), String> {
     use tauri_plugin_updater::UpdaterExt;
 
     tracing::info!("Checking for updates...");
@@ -295,7 +1213,7 @@ async fn check_for_updates(app: AppHandle) {
         Ok(u) => u,
         Err(e) => {
             tracing::warn!("Failed to create updater: {e}");
-            return;
+            return Err(e.to_string());
         }
     };
     match updater.check().await {
@@ -311,13 +1229,228 @@ async fn check_for_updates(app: AppHandle) {
             }
             drop(guard);
 
-            *state.update_available.lock().unwrap() = Some(version);
+            *state.update_available.lock().unwrap() = Some(version.clone());
+            let _ = app.emit("update-available", version);
         }
         Ok(None) => {
             tracing::info!("No updates available");
         }
         Err(e) => {
             tracing::warn!("Update check failed: {e}");
+            return Err(e.to_string());
         }
     }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn truncate_end(text: &str, max_len: usize, at_word_boundary: bool) -> String {
+        truncate_tray_label(text, max_len, at_word_boundary, TruncationStyle::End)
+    }
+
+    #[test]
+    fn tray_label_fits_untouched() {
+        assert_eq!(truncate_end("Short", 10, true), "Short");
+    }
+
+    #[test]
+    fn tray_label_backs_off_to_word_boundary() {
+        assert_eq!(
+            truncate_end("Hello wonderful world", 10, false),
+            "Hello won\u{2026}"
+        );
+        assert_eq!(
+            truncate_end("Hello wonderful world", 10, true),
+            "Hello\u{2026}"
+        );
+        assert_eq!(
+            truncate_end("Hello world again", 12, true),
+            "Hello world\u{2026}"
+        );
+    }
+
+    #[test]
+    fn tray_label_keeps_cut_when_boundary_is_too_early() {
+        assert_eq!(
+            truncate_end("A verylongwordhere", 10, true),
+            "A verylon\u{2026}"
+        );
+    }
+
+    #[test]
+    fn tray_label_word_boundary_with_multibyte_text() {
+        assert_eq!(
+            truncate_end("Café crème brûlée", 12, true),
+            "Café crème\u{2026}"
+        );
+        assert_eq!(
+            truncate_end("日本語のタイトル", 5, true),
+            "日本語の\u{2026}"
+        );
+    }
+
+    #[test]
+    fn log_fields_are_capped() {
+        let long = "é".repeat(200);
+        let truncated = truncate_for_log(&long);
+        assert_eq!(truncated.chars().count(), LOG_FIELD_MAX_CHARS);
+        assert!(truncated.ends_with('\u{2026}'));
+        assert_eq!(truncate_for_log("Short title"), "Short title");
+    }
+
+    #[test]
+    fn wake_detected_only_past_interval_plus_slack() {
+        let expected = Duration::from_secs(5);
+        assert!(!is_wake_from_sleep(expected, expected, 30));
+        assert!(!is_wake_from_sleep(Duration::from_secs(35), expected, 30));
+        assert!(is_wake_from_sleep(Duration::from_secs(36), expected, 30));
+    }
+
+    #[test]
+    fn wake_slack_scales_with_long_intervals() {
+        let expected = Duration::from_secs(120);
+        assert!(!is_wake_from_sleep(Duration::from_secs(180), expected, 10));
+        assert!(is_wake_from_sleep(Duration::from_secs(181), expected, 10));
+    }
+
+    fn identity(name: &str, album: &str) -> Option<(String, String, String)> {
+        Some((name.to_string(), "Artist".to_string(), album.to_string()))
+    }
+
+    #[test]
+    fn track_change_confirmed_on_second_matching_read() {
+        let mut pending = None;
+        assert!(awaiting_confirmation(
+            &mut pending,
+            identity("Next", "Album")
+        ));
+        assert!(!awaiting_confirmation(
+            &mut pending,
+            identity("Next", "Album")
+        ));
+        assert_eq!(pending, None);
+    }
+
+    #[test]
+    fn inconsistent_transition_read_is_not_confirmed() {
+        let mut pending = None;
+        // Mid-transition: the new name with the old album
+        assert!(awaiting_confirmation(
+            &mut pending,
+            identity("Next", "Old Album")
+        ));
+        // The settled read disagrees, so it starts its own confirmation
+        assert!(awaiting_confirmation(
+            &mut pending,
+            identity("Next", "New Album")
+        ));
+        assert!(!awaiting_confirmation(
+            &mut pending,
+            identity("Next", "New Album")
+        ));
+    }
+
+    #[test]
+    fn transient_read_dropped_when_track_settles_back() {
+        let mut pending = None;
+        assert!(awaiting_confirmation(
+            &mut pending,
+            identity("Next", "Old Album")
+        ));
+        // Back to the previous track: no change to confirm
+        assert!(!awaiting_confirmation(&mut pending, None));
+        assert_eq!(pending, None);
+    }
+
+    fn track_at(position_secs: f64, duration_secs: f64) -> apple_music::TrackInfo {
+        apple_music::TrackInfo {
+            position_secs,
+            duration_secs,
+            ..template::sample_track()
+        }
+    }
+
+    #[test]
+    fn restart_from_late_in_track_is_a_repeat() {
+        assert!(is_repeat_restart(230.0, &track_at(1.0, 240.0)));
+        assert!(is_repeat_restart(120.0, &track_at(0.0, 240.0)));
+    }
+
+    #[test]
+    fn early_scrub_back_is_not_a_repeat() {
+        assert!(!is_repeat_restart(60.0, &track_at(1.0, 240.0)));
+    }
+
+    #[test]
+    fn position_not_near_start_is_not_a_repeat() {
+        assert!(!is_repeat_restart(230.0, &track_at(10.0, 240.0)));
+    }
+
+    #[test]
+    fn streams_never_repeat() {
+        assert!(!is_repeat_restart(3600.0, &track_at(1.0, 0.0)));
+    }
+
+    fn track_from(
+        source_kind: TrackSourceKind,
+        artist: &str,
+        album: &str,
+    ) -> apple_music::TrackInfo {
+        apple_music::TrackInfo {
+            source_kind,
+            artist: artist.to_string(),
+            album: album.to_string(),
+            ..template::sample_track()
+        }
+    }
+
+    #[test]
+    fn itunes_lookup_depends_on_source_kind() {
+        assert!(worth_itunes_lookup(&track_from(
+            TrackSourceKind::Catalog,
+            "",
+            ""
+        )));
+        assert!(worth_itunes_lookup(&track_from(
+            TrackSourceKind::Local,
+            "Artist",
+            "Album"
+        )));
+        assert!(!worth_itunes_lookup(&track_from(
+            TrackSourceKind::Local,
+            "Artist",
+            " "
+        )));
+        assert!(!worth_itunes_lookup(&track_from(
+            TrackSourceKind::Stream,
+            "Artist",
+            "Album"
+        )));
+    }
+
+    #[test]
+    fn hide_local_files_blocks_only_local_tracks() {
+        let cfg = AppConfig {
+            hide_local_files: true,
+            ..AppConfig::default()
+        };
+        let state = AppState::new(
+            DiscordManager::disabled(),
+            Arc::new(Mutex::new(cfg.clone())),
+            true,
+        );
+        let local = track_from(TrackSourceKind::Local, "Artist", "Album");
+        let catalog = track_from(TrackSourceKind::Catalog, "Artist", "Album");
+        assert_eq!(
+            presence_block(&state, &cfg, Some(&local)),
+            Some(PresenceBlock::LocalFile)
+        );
+        assert_eq!(presence_block(&state, &cfg, Some(&catalog)), None);
+
+        let shown = AppConfig::default();
+        assert_eq!(presence_block(&state, &shown, Some(&local)), None);
+    }
 }
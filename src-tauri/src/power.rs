@@ -0,0 +1,20 @@
+use std::process::Command;
+
+/// Whether the Mac is running on battery, from the first line of `pmset -g batt`
+/// ("Now drawing from 'Battery Power'"). `None` when it can't be told, e.g. `pmset`
+/// failed or printed something unexpected.
+pub fn on_battery() -> Option<bool> {
+    let output = Command::new("pmset").args(["-g", "batt"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let source = stdout.lines().next()?;
+    if source.contains("'Battery Power'") {
+        Some(true)
+    } else if source.contains("'AC Power'") {
+        Some(false)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,21 @@
+use crate::apple_music::{AppleMusicError, TrackInfo};
+
+/// A player the poll loop can read the current track from.
+///
+/// `AppleMusicSource` (AppleScript) is the only implementation. There is no MediaRemote
+/// source, experimental or otherwise: MediaRemote is a private framework whose now-playing
+/// API delivers results through Objective-C blocks, which this crate has no bindings for,
+/// and recent macOS releases withhold that data from unentitled apps anyway.
+pub trait MusicSource: Send + Sync {
+    /// Short name for logs.
+    fn name(&self) -> &'static str;
+
+    /// Read the current track. Blocking; the poll loop calls this off the async runtime.
+    fn current_track(&self) -> Result<TrackInfo, AppleMusicError>;
+
+    /// Artwork URL the player itself reports for `track`, if it has one. When this
+    /// returns `None` the iTunes lookup in `AlbumArtResolver` is used instead.
+    fn artwork_url(&self, _track: &TrackInfo) -> Option<String> {
+        None
+    }
+}
@@ -1,32 +1,203 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
+use serde::Serialize;
 use tauri::menu::{CheckMenuItem, MenuItem};
 use tauri::Wry;
 
+use crate::album_art::AlbumArtResolver;
 use crate::apple_music::TrackInfo;
-use crate::config::AppConfig;
+use crate::config::{AppConfig, DisplayFormat};
 use crate::discord_rpc::DiscordManager;
 
+/// Why the poll loop chose its current interval.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PollReason {
+    Playing,
+    Paused,
+    /// Minimal mode polls at the paused rate regardless of playback.
+    Minimal,
+    /// A new track is being re-read before it's pushed to Discord.
+    Confirming,
+    /// Polling was turned off from the tray; the loop only idles.
+    PollingPaused,
+}
+
+/// The poll loop's current sleep between reads.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectivePollInterval {
+    pub interval_secs: f64,
+    pub reason: PollReason,
+}
+
 pub struct AppState {
     pub current_track: Mutex<Option<TrackInfo>>,
+    pub current_artwork_url: Mutex<Option<String>>,
+    /// Recently played tracks, most recent first.
+    pub track_history: Mutex<VecDeque<TrackInfo>>,
+    /// Set while a manually chosen presence is shown; the poll loop leaves Discord alone.
+    pub manual_override: AtomicBool,
+    /// Set while `auto_stop_after_hours` is holding presence cleared.
+    pub auto_stopped: AtomicBool,
     pub discord: DiscordManager,
+    pub art_resolver: tokio::sync::Mutex<AlbumArtResolver>,
     pub config: Arc<Mutex<AppConfig>>,
     pub now_playing_item: Mutex<Option<MenuItem<Wry>>>,
+    pub progress_item: Mutex<Option<MenuItem<Wry>>>,
     pub toggle_presence_item: Mutex<Option<CheckMenuItem<Wry>>>,
+    pub display_format_items: Mutex<Vec<(DisplayFormat, CheckMenuItem<Wry>)>>,
     pub update_item: Mutex<Option<MenuItem<Wry>>>,
     pub update_available: Mutex<Option<String>>,
+    pub update_installing: AtomicBool,
+    /// When "Check for Updates" was last run from the tray, for its cooldown.
+    pub last_update_check: Mutex<Option<Instant>>,
+    /// Error from the last automatic update check, once its retries ran out.
+    pub update_check_error: Mutex<Option<String>>,
+    pub safe_mode: bool,
+    /// Set to make the poll loop re-push presence on its next iteration.
+    pub force_resync: AtomicBool,
+    /// Cleared to freeze the poll loop; presence and tray label keep their last values.
+    pub polling_enabled: AtomicBool,
+    pub polling_item: Mutex<Option<CheckMenuItem<Wry>>>,
+    /// Published by the poll loop each iteration; `None` until the first one.
+    pub effective_poll_interval: Mutex<Option<EffectivePollInterval>>,
+    /// Whether presence may be pushed, tracking `enable_on_launch`. Pushes happen under
+    /// this lock (see `push_presence`), so a toggle can't interleave with one in flight.
+    presence_gate: Mutex<bool>,
 }
 
 impl AppState {
-    pub fn new(discord: DiscordManager, config: Arc<Mutex<AppConfig>>) -> Self {
+    pub fn new(discord: DiscordManager, config: Arc<Mutex<AppConfig>>, safe_mode: bool) -> Self {
+        let mut art_resolver = AlbumArtResolver::new();
+        art_resolver.set_disabled(safe_mode);
+        crate::configure_art_resolver(&mut art_resolver, &config.lock().unwrap());
+        let presence_enabled = config.lock().unwrap().enable_on_launch;
+
         Self {
             current_track: Mutex::new(None),
+            current_artwork_url: Mutex::new(None),
+            track_history: Mutex::new(VecDeque::new()),
+            manual_override: AtomicBool::new(false),
+            auto_stopped: AtomicBool::new(false),
             discord,
+            art_resolver: tokio::sync::Mutex::new(art_resolver),
             config,
             now_playing_item: Mutex::new(None),
+            progress_item: Mutex::new(None),
             toggle_presence_item: Mutex::new(None),
+            display_format_items: Mutex::new(Vec::new()),
             update_item: Mutex::new(None),
             update_available: Mutex::new(None),
+            update_installing: AtomicBool::new(false),
+            last_update_check: Mutex::new(None),
+            update_check_error: Mutex::new(None),
+            safe_mode,
+            force_resync: AtomicBool::new(false),
+            polling_enabled: AtomicBool::new(true),
+            polling_item: Mutex::new(None),
+            effective_poll_interval: Mutex::new(None),
+            presence_gate: Mutex::new(presence_enabled),
         }
     }
+
+    /// Run `push` against Discord unless presence is turned off. Returns whether it ran.
+    pub fn push_presence(&self, push: impl FnOnce(&DiscordManager)) -> bool {
+        let gate = self.presence_gate.lock().unwrap();
+        if *gate {
+            push(&self.discord);
+        }
+        *gate
+    }
+
+    /// Open or close the presence gate. Closing clears presence before any later push
+    /// can go out; opening makes the poll loop re-push on its next iteration.
+    pub fn set_presence_gate(&self, open: bool) {
+        let mut gate = self.presence_gate.lock().unwrap();
+        *gate = open;
+        if open {
+            self.force_resync.store(true, Ordering::Relaxed);
+        } else {
+            self.discord.clear_presence();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+
+    fn state_with_presence(enabled: bool) -> AppState {
+        let config = AppConfig {
+            enable_on_launch: enabled,
+            ..AppConfig::default()
+        };
+        AppState::new(
+            DiscordManager::disabled(),
+            Arc::new(Mutex::new(config)),
+            true,
+        )
+    }
+
+    #[test]
+    fn closed_gate_blocks_pushes() {
+        let state = state_with_presence(false);
+        assert!(!state.push_presence(|_| panic!("pushed while presence is off")));
+    }
+
+    #[test]
+    fn closing_the_gate_blocks_later_pushes() {
+        let state = state_with_presence(true);
+        assert!(state.push_presence(|_| {}));
+        state.set_presence_gate(false);
+        assert!(!state.push_presence(|_| panic!("pushed after presence was turned off")));
+    }
+
+    #[test]
+    fn opening_the_gate_forces_a_resync() {
+        let state = state_with_presence(false);
+        state.set_presence_gate(true);
+        assert!(state.force_resync.load(Ordering::Relaxed));
+        assert!(state.push_presence(|_| {}));
+    }
+
+    #[test]
+    fn toggle_waits_for_push_in_flight() {
+        let state = Arc::new(state_with_presence(true));
+        let (entered_tx, entered_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+
+        let pusher = {
+            let state = Arc::clone(&state);
+            thread::spawn(move || {
+                state.push_presence(|_| {
+                    entered_tx.send(()).unwrap();
+                    release_rx.recv().unwrap();
+                })
+            })
+        };
+        entered_rx.recv().unwrap();
+
+        let (toggled_tx, toggled_rx) = mpsc::channel();
+        let toggler = {
+            let state = Arc::clone(&state);
+            thread::spawn(move || {
+                state.set_presence_gate(false);
+                toggled_tx.send(()).unwrap();
+            })
+        };
+        // The push holds the gate, so the toggle can't have returned yet
+        assert_eq!(toggled_rx.try_recv(), Err(mpsc::TryRecvError::Empty));
+
+        release_tx.send(()).unwrap();
+        assert!(pusher.join().unwrap());
+        toggled_rx.recv().unwrap();
+        toggler.join().unwrap();
+        assert!(!state.push_presence(|_| panic!("pushed after presence was turned off")));
+    }
 }
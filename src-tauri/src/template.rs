@@ -0,0 +1,129 @@
+use serde::Serialize;
+
+use crate::apple_music::{TrackInfo, TrackSourceKind};
+use crate::config::AppConfig;
+
+/// Placeholders understood by `render`, without braces.
+pub const PLACEHOLDERS: &[&str] = &[
+    "name",
+    "artist",
+    "album",
+    "albumArtist",
+    "grouping",
+    "added",
+    "comments",
+];
+
+/// Result of expanding a template against a track.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rendered {
+    pub text: String,
+    /// Placeholders not in `PLACEHOLDERS`; these are left in the output verbatim.
+    pub unknown_placeholders: Vec<String>,
+}
+
+fn placeholder_value(name: &str, track: &TrackInfo) -> Option<String> {
+    if !PLACEHOLDERS.contains(&name) {
+        return None;
+    }
+    let value = match name {
+        "name" => track.name.clone(),
+        "artist" => track.artist.clone(),
+        "album" => track.album.clone(),
+        "albumArtist" => track.album_artist.clone().unwrap_or_default(),
+        "grouping" => track.grouping.clone().unwrap_or_default(),
+        "added" => match track.added_days_ago {
+            Some(0) => "today".to_string(),
+            Some(1) => "yesterday".to_string(),
+            Some(days) => format!("{days} days ago"),
+            None => String::new(),
+        },
+        "comments" => track.comments.clone().unwrap_or_default(),
+        _ => String::new(),
+    };
+    Some(value)
+}
+
+/// Expand `{placeholder}`s in `template` with values from `track`. `{{` and `}}` produce
+/// literal braces. Unbalanced braces are an error.
+pub fn render(template: &str, track: &TrackInfo) -> Result<Rendered, String> {
+    let mut text = String::with_capacity(template.len());
+    let mut unknown_placeholders = Vec::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some('{') | None => return Err("Unclosed '{' in template".to_string()),
+                        Some(c) => name.push(c),
+                    }
+                }
+                match placeholder_value(&name, track) {
+                    Some(value) => text.push_str(&value),
+                    None => {
+                        text.push_str(&format!("{{{name}}}"));
+                        if !unknown_placeholders.contains(&name) {
+                            unknown_placeholders.push(name);
+                        }
+                    }
+                }
+            }
+            '}' => return Err("Unmatched '}' in template".to_string()),
+            c => text.push(c),
+        }
+    }
+
+    Ok(Rendered {
+        text,
+        unknown_placeholders,
+    })
+}
+
+/// Check both of `cfg`'s templates render, as `DisplayFormat::Custom` requires.
+pub fn validate_templates(cfg: &AppConfig) -> Result<(), String> {
+    let sample = sample_track();
+    render(cfg.details_template(), &sample)
+        .map_err(|e| format!("Invalid details template: {e}"))?;
+    render(cfg.state_template(), &sample).map_err(|e| format!("Invalid state template: {e}"))?;
+    Ok(())
+}
+
+/// Track used for template previews in settings.
+pub fn sample_track() -> TrackInfo {
+    TrackInfo {
+        name: "Bohemian Rhapsody".to_string(),
+        artist: "Queen".to_string(),
+        album: "A Night at the Opera".to_string(),
+        duration_secs: 354.0,
+        position_secs: 0.0,
+        is_playing: true,
+        album_artist: Some("Queen".to_string()),
+        grouping: None,
+        queue_index: None,
+        queue_total: None,
+        sample_rate: Some(44_100),
+        bit_rate: Some(256),
+        airplay_device: None,
+        added_days_ago: Some(3),
+        playlist_name: None,
+        rating: Some(100),
+        album_rating: None,
+        skip_count: 0,
+        last_played_days_ago: Some(12),
+        comments: Some("Remastered 2011".to_string()),
+        source_kind: TrackSourceKind::Catalog,
+    }
+}
@@ -1,34 +1,244 @@
 use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
 
+use serde::Serialize;
 use tauri::image::Image;
-use tauri::menu::{CheckMenuItem, Menu, MenuItem, PredefinedMenuItem};
+use tauri::menu::{CheckMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu};
 use tauri::tray::TrayIconBuilder;
 use tauri::{App, AppHandle, Emitter, Manager};
 
-use crate::config;
+use crate::apple_music;
+use crate::clipboard::{self, CopyOutcome};
+use crate::commands::{self, CopyFormat};
+use crate::config::{self, DisplayFormat, NowPlayingClickAction, TrayProgressStyle};
 use crate::state::AppState;
+use crate::template;
 
-/// Relaunch the app after an update by spawning `open -a` with a short delay,
-/// then exiting the current process. `AppHandle::restart()` does not reliably
-/// relaunch macOS menu-bar apps, so we use `open` instead.
+/// Payload for the `update-progress` event emitted while an update downloads.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateProgress {
+    downloaded: u64,
+    total: Option<u64>,
+}
+
+/// The `.app` bundle the running binary lives in, or `None` outside one (e.g. a dev
+/// build). The executable path is canonicalized first so a symlink to the bundle
+/// resolves to the real one; only the `*.app/Contents/MacOS/<binary>` layout is
+/// assumed, not the bundle's name.
+fn app_bundle_path() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    let exe = exe.canonicalize().unwrap_or(exe);
+    let macos = exe.parent()?;
+    let contents = macos.parent()?;
+    let bundle = contents.parent()?;
+    let is_bundle = macos.file_name()? == "MacOS"
+        && contents.file_name()? == "Contents"
+        && bundle.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("app"));
+    is_bundle.then(|| bundle.to_path_buf())
+}
+
+/// Relaunch the app after an update by spawning `open` on the bundle after
+/// `relaunch_delay_secs`, then exiting the current process. `AppHandle::restart()` does
+/// not reliably relaunch macOS menu-bar apps, so it's only the fallback for when there's
+/// no bundle to open.
 fn relaunch_app(app: &AppHandle) {
-    if let Ok(exe) = std::env::current_exe() {
-        // Walk up from Contents/MacOS/binary to the .app bundle
-        if let Some(bundle) = exe.parent().and_then(|p| p.parent()).and_then(|p| p.parent()) {
-            let _ = std::process::Command::new("sh")
+    let state = app.state::<AppState>();
+    let delay_secs = state.config.lock().unwrap().relaunch_delay_secs;
+    // Every path below ends this process, so clear presence first like "quit" does
+    state.discord.shutdown();
+    match app_bundle_path() {
+        Some(bundle) => {
+            tracing::info!("Relaunching {} in {delay_secs}s", bundle.display());
+            // The path goes in as $0 so no quoting in the bundle name can break the command
+            let spawned = std::process::Command::new("sh")
                 .arg("-c")
-                .arg(format!("sleep 1 && open '{}'", bundle.display()))
+                .arg(format!("sleep {delay_secs} && open \"$0\""))
+                .arg(&bundle)
                 .spawn();
+            match spawned {
+                Ok(_) => app.exit(0),
+                Err(e) => {
+                    tracing::warn!("Failed to relaunch {}: {e}; restarting", bundle.display());
+                    app.restart();
+                }
+            }
+        }
+        None => {
+            tracing::info!("Not running from an app bundle; restarting in place");
+            app.restart();
+        }
+    }
+}
+
+/// Check the display format submenu item matching `format` and uncheck the rest.
+pub fn sync_display_format_items(state: &AppState, format: DisplayFormat) {
+    for (item_format, item) in state.display_format_items.lock().unwrap().iter() {
+        let _ = item.set_checked(*item_format == format);
+    }
+}
+
+/// Enable or disable the "Custom (Templates)" display format item.
+pub fn set_custom_format_enabled(state: &AppState, enabled: bool) {
+    for (item_format, item) in state.display_format_items.lock().unwrap().iter() {
+        if *item_format == DisplayFormat::Custom {
+            let _ = item.set_enabled(enabled);
         }
     }
-    app.exit(0);
+}
+
+const TRAY_ID: &str = "main";
+
+/// Set the text shown next to the tray icon in the menu bar; `None` shows the icon alone.
+pub fn set_tray_title(app: &AppHandle, title: Option<&str>) {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        let _ = tray.set_title(title);
+    }
+}
+
+/// Swap between the regular and the dimmed idle tray icon. Template mode is
+/// re-applied after every swap since `set_icon` resets it on macOS.
+pub fn set_tray_idle(app: &AppHandle, idle: bool) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let bytes: &[u8] = if idle {
+        include_bytes!("../icons/tray-idle.png")
+    } else {
+        include_bytes!("../icons/32x32.png")
+    };
+    match Image::from_bytes(bytes) {
+        Ok(icon) => {
+            let _ = tray.set_icon(Some(icon));
+            let _ = tray.set_icon_as_template(true);
+        }
+        Err(e) => tracing::warn!("Failed to load tray icon: {e}"),
+    }
+}
+
+/// Whether the now-playing item is clickable; track actions need a track.
+pub fn now_playing_item_enabled(action: NowPlayingClickAction, has_track: bool) -> bool {
+    match action {
+        NowPlayingClickAction::None => false,
+        NowPlayingClickAction::OpenSettings => true,
+        NowPlayingClickAction::OpenInMusic | NowPlayingClickAction::CopyNowPlaying => has_track,
+    }
+}
+
+pub fn show_settings(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("settings") {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+fn handle_now_playing_click(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let action = state.config.lock().unwrap().now_playing_click_action;
+    tracing::info!("Tray: now playing clicked ({action:?})");
+    match action {
+        NowPlayingClickAction::None => {}
+        NowPlayingClickAction::OpenInMusic => {
+            std::thread::spawn(|| {
+                if let Err(e) = apple_music::reveal_current_track() {
+                    tracing::warn!("Failed to reveal track in Music: {e}");
+                }
+            });
+        }
+        NowPlayingClickAction::CopyNowPlaying => {
+            let Some(track) = state.current_track.lock().unwrap().clone() else {
+                return;
+            };
+            let artwork_url = state.current_artwork_url.lock().unwrap().clone();
+            let text =
+                commands::format_now_playing(&track, artwork_url.as_deref(), CopyFormat::Plain);
+            if let Err(e) = clipboard::copy_text(&text) {
+                tracing::warn!("Failed to copy now playing: {e}");
+            }
+        }
+        NowPlayingClickAction::OpenSettings => show_settings(app),
+    }
+}
+
+/// Placeholder for the progress item while nothing is playing.
+pub const NO_PROGRESS_LABEL: &str = "--:--";
+
+/// "m:ss", or "h:mm:ss" from an hour up. Negative and non-finite input reads as 0:00.
+pub fn format_duration(secs: f64) -> String {
+    let total = if secs.is_finite() { secs.max(0.0) as u64 } else { 0 };
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+/// Text for the tray progress item. Tracks without a known duration (streams) always
+/// show the elapsed time.
+pub fn progress_label(position_secs: f64, duration_secs: f64, style: TrayProgressStyle) -> String {
+    if duration_secs <= 0.0 {
+        return format_duration(position_secs);
+    }
+    let position = position_secs.clamp(0.0, duration_secs);
+    match style {
+        TrayProgressStyle::Elapsed => format_duration(position),
+        TrayProgressStyle::Remaining => format!("-{}", format_duration(duration_secs - position)),
+        TrayProgressStyle::ElapsedTotal => {
+            format!("{} / {}", format_duration(position), format_duration(duration_secs))
+        }
+    }
+}
+
+/// Minimum time between manual update checks, so repeated clicks can't start a second
+/// download racing the first one's relaunch.
+const UPDATE_CHECK_COOLDOWN: Duration = Duration::from_secs(60);
+/// How long the tray shows "Checked Recently" before restoring the item's text.
+const CHECKED_RECENTLY_DISPLAY: Duration = Duration::from_secs(3);
+
+/// Start the manual update check cooldown. Returns `false` if it's still running.
+fn start_update_check_cooldown(app: &AppHandle) -> bool {
+    let state = app.state::<AppState>();
+    let mut last = state.last_update_check.lock().unwrap();
+    if last.is_some_and(|at| at.elapsed() < UPDATE_CHECK_COOLDOWN) {
+        return false;
+    }
+    *last = Some(Instant::now());
+    true
+}
+
+fn show_checked_recently(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let Some(item) = state.update_item.lock().unwrap().clone() else {
+        return;
+    };
+    let Ok(previous) = item.text() else {
+        return;
+    };
+    let _ = item.set_text("Checked Recently");
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(CHECKED_RECENTLY_DISPLAY).await;
+        // Leave it alone if an update check changed the text in the meantime
+        if item.text().is_ok_and(|text| text == "Checked Recently") {
+            let _ = item.set_text(previous);
+        }
+    });
 }
 
 pub fn setup_tray(app: &App) -> tauri::Result<()> {
     let state = app.state::<AppState>();
     let cfg = state.config.lock().unwrap().clone();
 
-    let now_playing = MenuItem::with_id(app, "now_playing", "Not Playing", false, None::<&str>)?;
+    let now_playing = MenuItem::with_id(
+        app,
+        "now_playing",
+        "Not Playing",
+        now_playing_item_enabled(cfg.now_playing_click_action, false),
+        None::<&str>,
+    )?;
+    let progress = MenuItem::with_id(app, "progress", NO_PROGRESS_LABEL, false, None::<&str>)?;
     let toggle_presence = CheckMenuItem::with_id(
         app,
         "toggle_presence",
@@ -37,6 +247,46 @@ pub fn setup_tray(app: &App) -> tauri::Result<()> {
         cfg.enable_on_launch,
         None::<&str>,
     )?;
+    let format_song_artist = CheckMenuItem::with_id(
+        app,
+        "format_song_artist",
+        "Song \u{2014} Artist",
+        true,
+        cfg.display_format == DisplayFormat::SongArtist,
+        None::<&str>,
+    )?;
+    let format_artist_song = CheckMenuItem::with_id(
+        app,
+        "format_artist_song",
+        "Artist \u{2014} Song",
+        true,
+        cfg.display_format == DisplayFormat::ArtistSong,
+        None::<&str>,
+    )?;
+    // Renders details_template / state_template, which are only set by editing
+    // config.json; disabled until one of them is
+    let format_custom = CheckMenuItem::with_id(
+        app,
+        "format_custom",
+        "Custom (Templates)",
+        cfg.has_custom_templates(),
+        cfg.display_format == DisplayFormat::Custom,
+        None::<&str>,
+    )?;
+    let display_format = Submenu::with_items(
+        app,
+        "Display Format",
+        true,
+        &[&format_song_artist, &format_artist_song, &format_custom],
+    )?;
+    let polling = CheckMenuItem::with_id(
+        app,
+        "toggle_polling",
+        "Poll Apple Music",
+        true,
+        true,
+        None::<&str>,
+    )?;
     let settings = MenuItem::with_id(app, "settings", "Settings...", true, None::<&str>)?;
     let copy_log = MenuItem::with_id(app, "copy_log", "Copy Debug Log", true, None::<&str>)?;
     let check_update =
@@ -47,8 +297,11 @@ pub fn setup_tray(app: &App) -> tauri::Result<()> {
         app,
         &[
             &now_playing,
+            &progress,
             &PredefinedMenuItem::separator(app)?,
             &toggle_presence,
+            &display_format,
+            &polling,
             &settings,
             &copy_log,
             &check_update,
@@ -60,13 +313,20 @@ pub fn setup_tray(app: &App) -> tauri::Result<()> {
     // Store menu item handles in state for later updates
     {
         *state.now_playing_item.lock().unwrap() = Some(now_playing);
+        *state.progress_item.lock().unwrap() = Some(progress);
         *state.toggle_presence_item.lock().unwrap() = Some(toggle_presence);
+        *state.display_format_items.lock().unwrap() = vec![
+            (DisplayFormat::SongArtist, format_song_artist),
+            (DisplayFormat::ArtistSong, format_artist_song),
+            (DisplayFormat::Custom, format_custom),
+        ];
+        *state.polling_item.lock().unwrap() = Some(polling);
         *state.update_item.lock().unwrap() = Some(check_update);
     }
 
     let icon = Image::from_bytes(include_bytes!("../icons/32x32.png"))?;
 
-    TrayIconBuilder::new()
+    TrayIconBuilder::with_id(TRAY_ID)
         .icon(icon)
         .icon_as_template(true)
         .menu(&menu)
@@ -82,31 +342,94 @@ pub fn setup_tray(app: &App) -> tauri::Result<()> {
                     .map(|item| item.is_checked().unwrap_or(false))
                     .unwrap_or(false);
 
+                // Close the gate before the config write and open it only after, so
+                // the poll loop never pushes against a half-applied toggle
+                if !is_checked {
+                    state.set_presence_gate(false);
+                }
                 {
                     let mut cfg = state.config.lock().unwrap();
                     cfg.enable_on_launch = is_checked;
                     let _ = config::save_config(&cfg);
                 }
+                if is_checked {
+                    state.set_presence_gate(true);
+                }
 
-                if !is_checked {
-                    state.discord.clear_presence();
+                let _ = app.emit("config-changed", ());
+            }
+            id @ ("format_song_artist" | "format_artist_song" | "format_custom") => {
+                let format = match id {
+                    "format_song_artist" => DisplayFormat::SongArtist,
+                    "format_artist_song" => DisplayFormat::ArtistSong,
+                    _ => DisplayFormat::Custom,
+                };
+                let state = app.state::<AppState>();
+
+                {
+                    let mut cfg = state.config.lock().unwrap();
+                    if format == DisplayFormat::Custom {
+                        if let Err(e) = template::validate_templates(&cfg) {
+                            tracing::warn!("Tray: not switching to custom format: {e}");
+                            let current = cfg.display_format;
+                            drop(cfg);
+                            // The click already toggled the item; put the checks back
+                            sync_display_format_items(&state, current);
+                            return;
+                        }
+                    }
+                    tracing::info!("Tray: display format set to {format:?}");
+                    cfg.display_format = format;
+                    let _ = config::save_config(&cfg);
                 }
 
+                sync_display_format_items(&state, format);
+                state.force_resync.store(true, Ordering::Relaxed);
                 let _ = app.emit("config-changed", ());
             }
+            "toggle_polling" => {
+                let state = app.state::<AppState>();
+                let enabled = state
+                    .polling_item
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .map(|item| item.is_checked().unwrap_or(true))
+                    .unwrap_or(true);
+                tracing::info!("Tray: polling {}", if enabled { "resumed" } else { "paused" });
+                state.polling_enabled.store(enabled, Ordering::Relaxed);
+            }
+            "now_playing" => handle_now_playing_click(app),
             "settings" => {
                 tracing::info!("Tray: opening Settings");
-                if let Some(window) = app.get_webview_window("settings") {
-                    let _ = window.show();
-                    let _ = window.set_focus();
-                }
+                show_settings(app);
             }
             "copy_log" => {
                 tracing::info!("Tray: copying debug log to clipboard");
-                copy_debug_log();
+                match copy_debug_log() {
+                    Ok(CopyOutcome::Copied) => {}
+                    Ok(CopyOutcome::SavedToFile { path }) => {
+                        // No clipboard access; reveal the saved file so the log isn't lost
+                        let _ = std::process::Command::new("open").arg("-R").arg(path).spawn();
+                    }
+                    Err(e) => tracing::warn!("Failed to copy debug log: {e}"),
+                }
             }
             "check_update" => {
                 tracing::info!("Tray: checking for updates");
+                if app.state::<AppState>().safe_mode {
+                    tracing::warn!("Update check skipped: safe mode is active");
+                    return;
+                }
+                if app.state::<AppState>().update_installing.load(Ordering::Relaxed) {
+                    tracing::info!("Update already installing; ignoring");
+                    return;
+                }
+                if !start_update_check_cooldown(app) {
+                    tracing::info!("Update checked recently; ignoring");
+                    show_checked_recently(app);
+                    return;
+                }
                 let app_handle = app.clone();
                 tauri::async_runtime::spawn(async move {
                     use tauri_plugin_updater::UpdaterExt;
@@ -131,15 +454,31 @@ pub fn setup_tray(app: &App) -> tauri::Result<()> {
                                     let _ = item.set_text(format!("Updating to v{version}..."));
                                 }
                             }
+                            *state.update_available.lock().unwrap() = Some(version.clone());
+                            state.update_installing.store(true, Ordering::Relaxed);
+
+                            let _ = app_handle.emit("update-available", &version);
+
+                            let progress_handle = app_handle.clone();
+                            let mut downloaded: u64 = 0;
+                            let on_chunk = move |chunk_len: usize, total: Option<u64>| {
+                                downloaded += chunk_len as u64;
+                                let _ = progress_handle.emit(
+                                    "update-progress",
+                                    UpdateProgress { downloaded, total },
+                                );
+                            };
 
-                            match update.download_and_install(|_, _| {}, || {}).await {
+                            match update.download_and_install(on_chunk, || {}).await {
                                 Ok(()) => {
                                     tracing::info!("Update installed, relaunching...");
+                                    let _ = app_handle.emit("update-installed", &version);
                                     relaunch_app(&app_handle);
                                 }
                                 Err(e) => {
                                     tracing::warn!("Update install failed: {e}");
                                     let state = app_handle.state::<AppState>();
+                                    state.update_installing.store(false, Ordering::Relaxed);
                                     let guard = state.update_item.lock().unwrap();
                                     if let Some(item) = guard.as_ref() {
                                         let _ = item.set_text("Check for Updates");
@@ -158,6 +497,7 @@ pub fn setup_tray(app: &App) -> tauri::Result<()> {
             }
             "quit" => {
                 tracing::info!("Tray: quitting");
+                app.state::<AppState>().discord.shutdown();
                 app.exit(0);
             }
             _ => {}
@@ -167,7 +507,7 @@ pub fn setup_tray(app: &App) -> tauri::Result<()> {
     Ok(())
 }
 
-fn copy_debug_log() {
+fn copy_debug_log() -> Result<CopyOutcome, String> {
     let log_dir = dirs::home_dir()
         .unwrap_or_else(|| std::path::PathBuf::from("."))
         .join(".amdp")
@@ -191,34 +531,18 @@ fn copy_debug_log() {
     };
 
     let Some(entry) = latest else {
-        tracing::warn!("No log files found in {}", log_dir.display());
-        return;
+        return Err(format!("No log files found in {}", log_dir.display()));
     };
 
     // Read last 100 lines
     let path = entry.path();
-    let file = match std::fs::File::open(&path) {
-        Ok(f) => f,
-        Err(e) => {
-            tracing::warn!("Failed to open log file: {e}");
-            return;
-        }
-    };
+    let file = std::fs::File::open(&path).map_err(|e| format!("Failed to open log file: {e}"))?;
 
     let lines: Vec<String> = BufReader::new(file).lines().map_while(Result::ok).collect();
     let tail: Vec<&String> = lines.iter().rev().take(100).collect::<Vec<_>>();
     let text: String = tail.into_iter().rev().cloned().collect::<Vec<_>>().join("\n");
 
-    match arboard::Clipboard::new() {
-        Ok(mut clipboard) => {
-            if let Err(e) = clipboard.set_text(&text) {
-                tracing::warn!("Failed to copy to clipboard: {e}");
-            } else {
-                tracing::info!("Copied {} lines from log to clipboard", lines.len().min(100));
-            }
-        }
-        Err(e) => {
-            tracing::warn!("Failed to access clipboard: {e}");
-        }
-    }
+    let outcome = clipboard::copy_text(&text)?;
+    tracing::info!("Copied {} lines from log ({outcome:?})", lines.len().min(100));
+    Ok(outcome)
 }
@@ -8,6 +8,26 @@ interface TrackInfo {
   durationSecs: number;
   positionSecs: number;
   isPlaying: boolean;
+  albumArtist: string | null;
+  grouping: string | null;
+  queueIndex: number | null;
+  queueTotal: number | null;
+  sampleRate: number | null;
+  bitRate: number | null;
+  airplayDevice: string | null;
+  addedDaysAgo: number | null;
+  playlistName: string | null;
+  rating: number | null;
+  albumRating: number | null;
+  skipCount: number;
+  lastPlayedDaysAgo: number | null;
+  comments: string | null;
+  sourceKind: "catalog" | "local" | "stream";
+}
+
+interface ArtResolved {
+  key: string;
+  url: string | null;
 }
 
 type DiscordStatus =
@@ -75,4 +95,15 @@ window.addEventListener("DOMContentLoaded", async () => {
     updateDisplay(event.payload);
     await refreshDiscordStatus();
   });
+  await listen<DiscordStatus>("discord-status-changed", (event) => {
+    updateDiscordStatus(event.payload);
+  });
+
+  const artEl = document.getElementById("track-art")!;
+  await listen<string>("art-resolving", () => {
+    artEl.textContent = "Loading\u2026";
+  });
+  await listen<ArtResolved>("art-resolved", (event) => {
+    artEl.textContent = event.payload.url ? "Found" : "Not found";
+  });
 });
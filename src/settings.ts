@@ -6,10 +6,69 @@ interface AppConfig {
   enableOnLaunch: boolean;
   showAlbumArt: boolean;
   showTimestamps: boolean;
+  timestampAnchor: string;
+  sessionGapSecs: number;
   displayFormat: string;
+  detailsField: string | null;
+  stateField: string | null;
   idleBehavior: string;
   pollIntervalSecs: number;
+  pollIntervalPlayingSecs: number | null;
+  pollIntervalPausedSecs: number | null;
   launchAtLogin: boolean;
+  rejectSmallArt: boolean;
+  minArtBytes: number;
+  autoUpdateCheck: boolean;
+  updateCheckDelaySecs: number;
+  updateCheckIntervalSecs: number;
+  updateCheckRetries: number;
+  updateFailureRecheckSecs: number;
+  relaunchDelaySecs: number;
+  maxMemoryArtEntries: number;
+  maxDiskArtEntries: number;
+  fallbackElapsedTimestamp: boolean;
+  artWaitTimeoutMs: number;
+  truncateTrayAtWordBoundary: boolean;
+  showTrackInMenubar: boolean;
+  trayProgressStyle: string;
+  showQueueAsParty: boolean;
+  minTrackDurationSecs: number;
+  smallImageMode: string;
+  musicQuitBehavior: string;
+  musicQuitGraceSecs: number;
+  idleTrayIcon: string;
+  showQualityBadge: boolean;
+  showRatingStars: boolean;
+  ratingSource: string;
+  wakeDetectionSlackSecs: number;
+  detailsTemplate: string | null;
+  stateTemplate: string | null;
+  hideExplicitArt: boolean;
+  fixedLargeImageUrl: string | null;
+  verifyArtReachable: boolean;
+  refreshExpiringArt: boolean;
+  offlineStaleArt: boolean;
+  pauseArtOnBattery: boolean;
+  truncationStyle: string;
+  pausedHideArt: boolean;
+  showVersionInPresence: boolean;
+  artConcurrency: number;
+  itunesResultLimit: number;
+  autoStopAfterHours: number | null;
+  airplayBehavior: string;
+  artPlacement: string;
+  logDiscordPayloads: boolean;
+  maxReconnectAttempts: number | null;
+  presenceHeartbeatSecs: number | null;
+  minimalMode: boolean;
+  newBadgeDays: number | null;
+  nowPlayingClickAction: string;
+  hideLocalFiles: boolean;
+  broadcastOnlyPlaylists: string[];
+  trackEventMinIntervalSecs: number;
+  onTrackChangeScript: string | null;
+  onPauseScript: string | null;
+  idlePresence: string | null;
 }
 
 const els = {
@@ -31,7 +90,12 @@ const els = {
     document.getElementById("launch-at-login") as HTMLInputElement,
 };
 
+// Last config received from the backend. Fields without a form control are
+// carried through unchanged so saving never resets them to their defaults.
+let currentConfig: AppConfig | null = null;
+
 function populateForm(config: AppConfig) {
+  currentConfig = config;
   els.enableOnLaunch().checked = config.enableOnLaunch;
   els.showAlbumArt().checked = config.showAlbumArt;
   els.showTimestamps().checked = config.showTimestamps;
@@ -44,6 +108,7 @@ function populateForm(config: AppConfig) {
 
 function readForm(): AppConfig {
   return {
+    ...(currentConfig as AppConfig),
     enableOnLaunch: els.enableOnLaunch().checked,
     showAlbumArt: els.showAlbumArt().checked,
     showTimestamps: els.showTimestamps().checked,
@@ -54,13 +119,18 @@ function readForm(): AppConfig {
   };
 }
 
+// Set while a save is scheduled but hasn't started yet
 let saveTimer: ReturnType<typeof setTimeout> | null = null;
 
 function scheduleSave() {
   if (saveTimer) clearTimeout(saveTimer);
   saveTimer = setTimeout(async () => {
+    saveTimer = null;
     const newConfig = readForm();
-    await invoke("save_config", { newConfig });
+    const saved = await invoke<AppConfig>("save_config", { newConfig });
+    // Show values as clamped by the backend, unless the user changed something
+    // meanwhile: repopulating would revert that edit before its own save reads it
+    if (!saveTimer) populateForm(saved);
   }, 300);
 }
 
@@ -77,6 +147,21 @@ window.addEventListener("DOMContentLoaded", async () => {
     els.pollIntervalValue().textContent = `${els.pollInterval().value}s`;
   });
 
+  // Copy a diagnostics snapshot for bug reports
+  const copyDiagnostics = document.getElementById(
+    "copy-diagnostics",
+  ) as HTMLButtonElement;
+  copyDiagnostics.addEventListener("click", async () => {
+    try {
+      const outcome = await invoke<{ kind: string }>("copy_diagnostics");
+      copyDiagnostics.textContent =
+        outcome.kind === "savedToFile" ? "Saved to file" : "Copied";
+    } catch {
+      copyDiagnostics.textContent = "Failed";
+    }
+    setTimeout(() => (copyDiagnostics.textContent = "Copy"), 1500);
+  });
+
   // Auto-save on any change
   const inputs = document.querySelectorAll("input, select");
   inputs.forEach((el) => {
@@ -86,6 +171,8 @@ window.addEventListener("DOMContentLoaded", async () => {
   // Sync when config changes externally (e.g. tray toggle)
   await listen("config-changed", async () => {
     const updated = await invoke<AppConfig>("get_config");
-    populateForm(updated);
+    // A pending save would otherwise read the reverted form; its own
+    // config-changed brings the form up to date afterwards
+    if (!saveTimer) populateForm(updated);
   });
 });